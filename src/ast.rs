@@ -1,48 +1,184 @@
+use std::borrow::Cow;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub(crate) struct GhostJson {
     update: String,
     #[serde(rename = "ghostList")]
     ghost_list: Vec<GhostData>,
+    /// records of erased ghost data, to keep them from coming back by merge.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tombstones: Vec<Tombstone>,
+    /// fields unknown to this tool. kept as is through load and write.
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(from = "GhostDataRepr", into = "GhostDataRepr")]
 pub(crate) struct GhostData {
+    directory: String,
+    sakura_name: String,
+    /// `None` when the ghost has no `kero.name`, `Some("")` when it is blank.
+    kero_name: Option<String>,
+    /// labels curated by hand, e.g. `retired`, `test`, `private`.
+    tags: Vec<String>,
+    /// free-form note curated by hand.
+    note: Option<String>,
+    /// fields unknown to this tool. kept as is through load and write.
+    extra: Map<String, Value>,
+}
+
+/// fields which identify a ghost, see `GhostData::key`.
+pub(crate) type GhostKey<'a> = (Cow<'a, str>, &'a str, Option<&'a str>);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub(crate) struct Tombstone {
+    ghost: GhostData,
+    #[serde(rename = "erasedAt")]
+    erased_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// representation of `GhostData` in json.
+/// `keroName` is always a string for compatibility, and `noKero` marks a ghost without kero.
+/// lists written by older versions have `""` for both, so a blank kero needs `blankKero`.
+#[derive(Serialize, Deserialize)]
+struct GhostDataRepr {
     directory: String,
     #[serde(rename = "sakuraName")]
     sakura_name: String,
-    #[serde(rename = "keroName")]
-    kero_name: String,
+    #[serde(rename = "keroName", default)]
+    kero_name: Option<String>,
+    #[serde(rename = "noKero", default, skip_serializing_if = "std::ops::Not::not")]
+    no_kero: bool,
+    #[serde(
+        rename = "blankKero",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    blank_kero: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl From<GhostDataRepr> for GhostData {
+    fn from(value: GhostDataRepr) -> Self {
+        GhostData {
+            directory: value.directory,
+            sakura_name: value.sakura_name,
+            kero_name: value
+                .kero_name
+                .filter(|v| !value.no_kero && (!v.is_empty() || value.blank_kero)),
+            tags: value.tags,
+            note: value.note,
+            extra: value.extra,
+        }
+    }
+}
+
+impl From<GhostData> for GhostDataRepr {
+    fn from(value: GhostData) -> Self {
+        GhostDataRepr {
+            directory: value.directory,
+            sakura_name: value.sakura_name,
+            no_kero: value.kero_name.is_none(),
+            blank_kero: value.kero_name.as_ref().is_some_and(|v| v.is_empty()),
+            kero_name: Some(value.kero_name.unwrap_or_default()),
+            tags: value.tags,
+            note: value.note,
+            extra: value.extra,
+        }
+    }
 }
 
 impl GhostJson {
     pub fn new(update: DateTime<Utc>, ghost_list: Vec<GhostData>) -> GhostJson {
         let update = update.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
 
-        GhostJson { update, ghost_list }
+        GhostJson {
+            update,
+            ghost_list,
+            tombstones: Vec::new(),
+            extra: Map::new(),
+        }
+    }
+
+    /// copy of `self` updated now with `ghost_list`, keeping tombstones and unknown fields.
+    pub fn updated(&self, ghost_list: Vec<GhostData>) -> GhostJson {
+        GhostJson::new(Utc::now(), ghost_list)
+            .with_tombstones(self.tombstones.clone())
+            .with_extra(self.extra.clone())
+    }
+
+    /// copy of `self` with raw `update`, which may not be RFC 3339.
+    pub fn with_update(mut self, update: String) -> GhostJson {
+        self.update = update;
+        self
+    }
+
+    pub fn with_ghost_list(mut self, ghost_list: Vec<GhostData>) -> GhostJson {
+        self.ghost_list = ghost_list;
+        self
+    }
+
+    pub fn with_tombstones(mut self, tombstones: Vec<Tombstone>) -> GhostJson {
+        self.tombstones = tombstones;
+        self
+    }
+
+    pub fn with_extra(mut self, extra: Map<String, Value>) -> GhostJson {
+        self.extra = extra;
+        self
     }
 
-    #[cfg(test)]
     pub fn update(&self) -> &String {
         &self.update
     }
 
+    /// `update` as date-time, or `None` when it is not RFC 3339.
+    pub fn update_time(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.update)
+    }
+
     pub fn ghost_list(&self) -> &Vec<GhostData> {
         &self.ghost_list
     }
+
+    pub fn tombstones(&self) -> &Vec<Tombstone> {
+        &self.tombstones
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
 }
 
 impl GhostData {
-    pub fn new(directory: String, sakura_name: String, kero_name: String) -> GhostData {
+    pub fn new(directory: String, sakura_name: String, kero_name: Option<String>) -> GhostData {
         GhostData {
             directory,
             sakura_name,
             kero_name,
+            tags: Vec::new(),
+            note: None,
+            extra: Map::new(),
         }
     }
 
+    pub fn with_extra(mut self, extra: Map<String, Value>) -> GhostData {
+        self.extra = extra;
+        self
+    }
+
     pub fn directory(&self) -> &String {
         &self.directory
     }
@@ -51,15 +187,237 @@ impl GhostData {
         &self.sakura_name
     }
 
-    pub fn kero_name(&self) -> &String {
-        &self.kero_name
+    pub fn kero_name(&self) -> Option<&String> {
+        self.kero_name.as_ref()
+    }
+
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    pub fn note(&self) -> Option<&String> {
+        self.note.as_ref()
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.tags.iter().any(|v| v == tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.retain(|v| v != tag);
+    }
+
+    pub fn clear_tags(&mut self) {
+        self.tags.clear();
+    }
+
+    pub fn set_note(&mut self, note: Option<String>) {
+        self.note = note;
+    }
+
+    /// whether `other` is the same ghost, ignoring tags, note and unknown fields.
+    pub fn is_same_ghost(&self, other: &GhostData) -> bool {
+        self.key() == other.key()
+    }
+
+    /// fields which identify a ghost. usable as a key of hash maps.
+    /// directory is compared as windows does, see `directory_key`.
+    pub fn key(&self) -> GhostKey<'_> {
+        (
+            directory_key(&self.directory),
+            &self.sakura_name,
+            self.kero_name.as_deref(),
+        )
+    }
+
+    /// takes tags, note and unknown fields of `other` which `self` does not have.
+    pub fn absorb(&mut self, other: &GhostData) {
+        self.absorb_annotations(other);
+        for (k, v) in other.extra.iter() {
+            if !self.extra.contains_key(k) {
+                self.extra.insert(k.clone(), v.clone());
+            }
+        }
+    }
+
+    /// takes tags and note of `other` which `self` does not have.
+    pub fn absorb_annotations(&mut self, other: &GhostData) {
+        for tag in other.tags.iter() {
+            self.add_tag(tag);
+        }
+        if self.note.is_none() {
+            self.note = other.note.clone();
+        }
     }
 }
 
+impl Tombstone {
+    pub fn new(ghost: &GhostData, erased_at: DateTime<Utc>, reason: Option<String>) -> Tombstone {
+        Tombstone {
+            ghost: GhostData::new(
+                ghost.directory.clone(),
+                ghost.sakura_name.clone(),
+                ghost.kero_name.clone(),
+            ),
+            erased_at: erased_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            reason,
+        }
+    }
+
+    pub fn ghost(&self) -> &GhostData {
+        &self.ghost
+    }
+
+    /// `erasedAt` as date-time, or `None` when it is not RFC 3339.
+    pub fn erased_at(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.erased_at)
+    }
+
+    #[cfg(test)]
+    pub fn reason(&self) -> Option<&String> {
+        self.reason.as_ref()
+    }
+}
+
+/// `directory` as windows compares install folders: case-insensitive,
+/// without trailing dots and spaces.
+pub(crate) fn directory_key(directory: &str) -> Cow<'_, str> {
+    let directory = directory.trim_end_matches(['.', ' ']);
+    if directory.chars().any(|c| c.is_uppercase()) {
+        Cow::Owned(directory.to_lowercase())
+    } else {
+        Cow::Borrowed(directory)
+    }
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|v| v.with_timezone(&Utc))
+}
+
+/// JSON Schema of `GhostJson`.
+pub(crate) fn json_schema() -> Value {
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "GhostJson",
+        "type": "object",
+        "required": ["update", "ghostList"],
+        "properties": {
+            "update": { "type": "string", "format": "date-time" },
+            "ghostList": {
+                "type": "array",
+                "items": { "$ref": "#/$defs/GhostData" }
+            },
+            "tombstones": {
+                "type": "array",
+                "items": { "$ref": "#/$defs/Tombstone" }
+            }
+        },
+        "$defs": {
+            "Tombstone": {
+                "type": "object",
+                "required": ["ghost", "erasedAt"],
+                "properties": {
+                    "ghost": { "$ref": "#/$defs/GhostData" },
+                    "erasedAt": { "type": "string", "format": "date-time" },
+                    "reason": { "type": "string" }
+                }
+            },
+            "GhostData": {
+                "type": "object",
+                "required": ["directory", "sakuraName", "keroName"],
+                "properties": {
+                    "directory": { "type": "string", "minLength": 1 },
+                    "sakuraName": { "type": "string", "minLength": 1 },
+                    "keroName": { "type": "string" },
+                    "noKero": { "type": "boolean" },
+                    "blankKero": { "type": "boolean" },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "note": { "type": "string" }
+                }
+            }
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    mod ghost_data {
+        use super::*;
+
+        mod absorb {
+            use super::*;
+
+            #[test]
+            fn taking_only_missing_fields() {
+                let mut extra_a = Map::new();
+                extra_a.insert("author".to_string(), Value::String("a".to_string()));
+                let mut v = GhostData::new("d".to_string(), "s".to_string(), Some("k".to_string()))
+                    .with_extra(extra_a);
+
+                let mut extra_b = Map::new();
+                extra_b.insert("author".to_string(), Value::String("b".to_string()));
+                extra_b.insert("url".to_string(), Value::String("b_url".to_string()));
+                let other = GhostData::new("d".to_string(), "s".to_string(), Some("k".to_string()))
+                    .with_extra(extra_b);
+
+                assert!(v.is_same_ghost(&other));
+                v.absorb(&other);
+                assert_eq!(
+                    v.extra().get("author"),
+                    Some(&Value::String("a".to_string()))
+                );
+                assert_eq!(
+                    v.extra().get("url"),
+                    Some(&Value::String("b_url".to_string()))
+                );
+            }
+        }
+
+        mod repr {
+            use super::*;
+
+            #[test]
+            fn no_kero_when_blank_in_older_format() {
+                let case = r#"[{"directory":"a","sakuraName":"s","keroName":""},{"directory":"a","sakuraName":"s","keroName":"","noKero":true},{"directory":"a","sakuraName":"s","keroName":"","blankKero":true}]"#;
+                let result: Vec<GhostData> = serde_json::from_str(case).unwrap();
+                assert_eq!(result[0].kero_name(), None);
+                assert_eq!(result[1].kero_name(), None);
+                assert_eq!(result[2].kero_name(), Some(&String::new()));
+                assert!(result[0].is_same_ghost(&result[1]));
+
+                assert_eq!(
+                    serde_json::to_string(&result).unwrap(),
+                    r#"[{"directory":"a","sakuraName":"s","keroName":"","noKero":true},{"directory":"a","sakuraName":"s","keroName":"","noKero":true},{"directory":"a","sakuraName":"s","keroName":"","blankKero":true}]"#
+                );
+            }
+        }
+    }
+
+    mod directory_key {
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            assert_eq!(directory_key("Emily"), "emily");
+            assert_eq!(directory_key("emily. ."), "emily");
+            assert!(matches!(directory_key("emily"), Cow::Borrowed(_)));
+            assert!(GhostData::new("Emily".to_string(), "s".to_string(), None)
+                .is_same_ghost(&GhostData::new("emily.".to_string(), "s".to_string(), None)));
+            assert!(!GhostData::new("Emily".to_string(), "s".to_string(), None)
+                .is_same_ghost(&GhostData::new("Emily".to_string(), "S".to_string(), None)));
+        }
+    }
+
     mod ghost_json {
         use super::*;
 
@@ -74,21 +432,25 @@ mod tests {
                 let t = NaiveTime::from_hms_milli_opt(7, 18, 31, 808).unwrap();
                 let update = NaiveDateTime::new(d, t).and_utc();
                 let ghost_list = vec![
-                    GhostData::new("100th_year".to_string(), "霊".to_string(), "".to_string()),
+                    GhostData::new(
+                        "100th_year".to_string(),
+                        "霊".to_string(),
+                        Some("".to_string()),
+                    ),
                     GhostData::new(
                         "FoxTheory".to_string(),
                         "リサ".to_string(),
-                        "book".to_string(),
+                        Some("book".to_string()),
                     ),
                     GhostData::new(
                         "tanumki".to_string(),
                         "きつね".to_string(),
-                        "たぬき".to_string(),
+                        Some("たぬき".to_string()),
                     ),
                     GhostData::new(
                         "tcidelam".to_string(),
                         "シデラム".to_string(),
-                        "".to_string(),
+                        Some("".to_string()),
                     ),
                 ];
                 let result = GhostJson::new(update, ghost_list.clone());
@@ -111,22 +473,34 @@ mod tests {
                         GhostData {
                             directory: "100th_year".to_string(),
                             sakura_name: "霊".to_string(),
-                            kero_name: "".to_string(),
+                            kero_name: None,
+                            tags: Vec::new(),
+                            note: None,
+                            extra: Map::new(),
                         },
                         GhostData {
                             directory: "FoxTheory".to_string(),
                             sakura_name: "リサ".to_string(),
-                            kero_name: "book".to_string(),
+                            kero_name: Some("book".to_string()),
+                            tags: Vec::new(),
+                            note: None,
+                            extra: Map::new(),
                         },
                         GhostData {
                             directory: "tanumki".to_string(),
                             sakura_name: "きつね".to_string(),
-                            kero_name: "たぬき".to_string(),
+                            kero_name: Some("たぬき".to_string()),
+                            tags: Vec::new(),
+                            note: None,
+                            extra: Map::new(),
                         },
                         GhostData {
                             directory: "tcidelam".to_string(),
                             sakura_name: "シデラム".to_string(),
-                            kero_name: "".to_string(),
+                            kero_name: None,
+                            tags: Vec::new(),
+                            note: None,
+                            extra: Map::new(),
                         },
                     ]
                 );
@@ -145,6 +519,61 @@ mod tests {
                 let case = r#"{"update":"2023-12-18T07:18:31.808Z"}"#;
                 assert!(serde_json::from_str::<GhostJson>(case).is_err());
             }
+
+            #[test]
+            fn keeping_unknown_fields() {
+                let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book","author":"tukinami"}],"source":"teammate"}"#;
+                let result: GhostJson = serde_json::from_str(case).unwrap();
+                assert_eq!(
+                    result.extra().get("source"),
+                    Some(&Value::String("teammate".to_string()))
+                );
+                assert_eq!(
+                    result.ghost_list()[0].extra().get("author"),
+                    Some(&Value::String("tukinami".to_string()))
+                );
+                assert_eq!(serde_json::to_string(&result).unwrap(), case);
+            }
+        }
+
+        mod kero_name {
+            use super::*;
+
+            #[test]
+            fn distinguishing_no_kero_from_blank_kero() {
+                let no_kero = GhostData::new("d".to_string(), "s".to_string(), None);
+                let blank_kero =
+                    GhostData::new("d".to_string(), "s".to_string(), Some("".to_string()));
+                assert!(!no_kero.is_same_ghost(&blank_kero));
+
+                let result = serde_json::to_string(&no_kero).unwrap();
+                assert_eq!(
+                    result,
+                    r#"{"directory":"d","sakuraName":"s","keroName":"","noKero":true}"#
+                );
+                assert_eq!(serde_json::from_str::<GhostData>(&result).unwrap(), no_kero);
+
+                let result = serde_json::to_string(&blank_kero).unwrap();
+                assert_eq!(
+                    result,
+                    r#"{"directory":"d","sakuraName":"s","keroName":"","blankKero":true}"#
+                );
+                assert_eq!(
+                    serde_json::from_str::<GhostData>(&result).unwrap(),
+                    blank_kero
+                );
+            }
+
+            #[test]
+            fn none_when_kero_name_is_missing() {
+                let case = r#"{"directory":"d","sakuraName":"s"}"#;
+                let result = serde_json::from_str::<GhostData>(case).unwrap();
+                assert_eq!(result.kero_name(), None);
+
+                let case = r#"{"directory":"d","sakuraName":"s","keroName":null}"#;
+                let result = serde_json::from_str::<GhostData>(case).unwrap();
+                assert_eq!(result.kero_name(), None);
+            }
         }
 
         mod serialize {
@@ -158,30 +587,44 @@ mod tests {
                         GhostData {
                             directory: "100th_year".to_string(),
                             sakura_name: "霊".to_string(),
-                            kero_name: "".to_string(),
+                            kero_name: Some("".to_string()),
+                            tags: Vec::new(),
+                            note: None,
+                            extra: Map::new(),
                         },
                         GhostData {
                             directory: "FoxTheory".to_string(),
                             sakura_name: "リサ".to_string(),
-                            kero_name: "book".to_string(),
+                            kero_name: Some("book".to_string()),
+                            tags: Vec::new(),
+                            note: None,
+                            extra: Map::new(),
                         },
                         GhostData {
                             directory: "tanumki".to_string(),
                             sakura_name: "きつね".to_string(),
-                            kero_name: "たぬき".to_string(),
+                            kero_name: Some("たぬき".to_string()),
+                            tags: Vec::new(),
+                            note: None,
+                            extra: Map::new(),
                         },
                         GhostData {
                             directory: "tcidelam".to_string(),
                             sakura_name: "シデラム".to_string(),
-                            kero_name: "".to_string(),
+                            kero_name: Some("".to_string()),
+                            tags: Vec::new(),
+                            note: None,
+                            extra: Map::new(),
                         },
                     ],
+                    tombstones: Vec::new(),
+                    extra: Map::new(),
                 };
 
                 let result = serde_json::to_string(&case).unwrap();
                 assert_eq!(
                     result,
-                    r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":""},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book"},{"directory":"tanumki","sakuraName":"きつね","keroName":"たぬき"},{"directory":"tcidelam","sakuraName":"シデラム","keroName":""}]}"#
+                    r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":"","blankKero":true},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book"},{"directory":"tanumki","sakuraName":"きつね","keroName":"たぬき"},{"directory":"tcidelam","sakuraName":"シデラム","keroName":"","blankKero":true}]}"#
                 );
             }
         }
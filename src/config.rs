@@ -3,18 +3,105 @@ use std::path::PathBuf;
 use clap::Parser;
 
 const DEFAULT_TARGET_PATH: &str = "./ghost_list.json";
+const DEFAULT_SCHEMA_PATH: &str = "./ghost_list.schema.json";
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
-pub(crate) enum Cli {
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    #[command(flatten)]
+    global_options: GlobalOptions,
+}
+
+#[derive(clap::Subcommand)]
+pub(crate) enum Command {
     /// append ghost data from directories to a json.
     Append(AppendArgs),
     /// build ghost data json from directories.
     Build(BuildArgs),
     /// erase ghost data from json.
     Erase(EraseArgs),
+    /// export ghost data json to other formats.
+    Export(ExportArgs),
+    /// import ghost data json from other formats.
+    Import(ImportArgs),
     /// merge ghost data jsons.
     Merge(MergeArgs),
+    /// set or clear the note of ghost data in json.
+    Note(NoteArgs),
+    /// write JSON Schema of ghost data json.
+    Schema(SchemaArgs),
+    /// add, remove or clear tags of ghost data in json.
+    Tag(TagArgs),
+    /// validate ghost data json and report problems.
+    Validate(ValidateArgs),
+}
+
+#[derive(clap::Args, Default)]
+pub(crate) struct GlobalOptions {
+    /// fields which identify a ghost when removing duplicates.
+    #[arg(long, global = true, value_enum, default_value_t = IdentityKey::All)]
+    key: IdentityKey,
+    /// compare names after normalizing width, case, spaces and invisible characters.
+    #[arg(long, global = true)]
+    normalize: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+pub(crate) enum IdentityKey {
+    /// directory, sakuraName and keroName.
+    #[default]
+    All,
+    /// directory only.
+    Directory,
+    /// sakuraName and keroName. a ghost without kero differs from one with blank kero.
+    Names,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+pub(crate) enum ConflictPolicy {
+    /// keep all versions.
+    #[default]
+    KeepAll,
+    /// prefer the version from the input with the newest `update`.
+    Newest,
+    /// prefer the version from the earliest input. for append, the existing json.
+    Priority,
+    /// prefer freshly scanned data. only for append.
+    Scanned,
+}
+
+#[derive(clap::Args, Default)]
+pub(crate) struct WriteOptions {
+    /// style of output json.
+    #[arg(long, value_enum, default_value_t = OutputStyle::Compact)]
+    style: OutputStyle,
+    /// format of output list. decided by extension when omitted.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    list_format: Option<ListFormat>,
+    /// number of backups to keep, as `<output>.1` (newest) to `<output>.<N>`.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    backups: usize,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+pub(crate) enum OutputStyle {
+    /// whole json on one line.
+    #[default]
+    Compact,
+    /// indented json.
+    Pretty,
+    /// entries sorted by directory and names, one entry per line.
+    Canonical,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ListFormat {
+    /// one json object with `update` and `ghostList`.
+    Json,
+    /// newline-delimited json: a header line with `update`, then one ghost data per line.
+    Ndjson,
 }
 
 #[derive(clap::Args)]
@@ -23,9 +110,23 @@ pub(crate) struct AppendArgs {
     /// paths to installed ghost directory. e.g. C:/SSP/ghost.
     #[arg(short, long, value_name = "DIRS")]
     input: Vec<PathBuf>,
-    /// output path.
+    /// output path. `-` for stdout.
     #[arg(short, long, value_name = "PATH", default_value = DEFAULT_TARGET_PATH)]
     output: PathBuf,
+    /// how to resolve conflicts between the json and scanned data.
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::KeepAll)]
+    policy: ConflictPolicy,
+    /// replace entries of the same directory with scanned data, instead of adding them.
+    #[arg(long, conflicts_with = "policy")]
+    upsert: bool,
+    /// path to write conflicts as json.
+    #[arg(long, value_name = "PATH", conflicts_with = "upsert")]
+    conflict_report: Option<PathBuf>,
+    /// recover well-formed entries when the json is partially invalid.
+    #[arg(long)]
+    lenient: bool,
+    #[command(flatten)]
+    write_options: WriteOptions,
 }
 
 #[derive(clap::Args)]
@@ -34,15 +135,17 @@ pub(crate) struct BuildArgs {
     /// paths to installed ghost directory. e.g. C:/SSP/ghost.
     #[arg(short, long, value_name = "DIRS")]
     input: Vec<PathBuf>,
-    /// output path.
+    /// output path. `-` for stdout.
     #[arg(short, long, value_name = "PATH", default_value = DEFAULT_TARGET_PATH)]
     output: PathBuf,
+    #[command(flatten)]
+    write_options: WriteOptions,
 }
 
 #[derive(clap::Args)]
 #[command(about, long_about = None)]
 pub(crate) struct EraseArgs {
-    /// path to target.
+    /// path to target. `-` for stdin and stdout.
     #[arg(short, long, value_name = "PATH", default_value = DEFAULT_TARGET_PATH)]
     target: PathBuf,
     /// directory name that you want to erase from target.
@@ -54,23 +157,262 @@ pub(crate) struct EraseArgs {
     /// keroName that you want to erase from target.
     #[arg(short, long, value_name = "NAME")]
     kero_name: Option<String>,
+    /// record tombstones of erased ghost data, so that merge does not bring them back.
+    #[arg(long)]
+    tombstone: bool,
+    /// reason recorded in tombstones.
+    #[arg(long, value_name = "TEXT", requires = "tombstone")]
+    reason: Option<String>,
+    #[command(flatten)]
+    write_options: WriteOptions,
+}
+
+#[derive(clap::Args)]
+#[command(about, long_about = None)]
+pub(crate) struct ExportArgs {
+    /// path to ghost data json. `-` for stdin.
+    #[arg(short, long, value_name = "PATH", default_value = DEFAULT_TARGET_PATH)]
+    input: PathBuf,
+    /// output path. `-` for stdout.
+    #[arg(short, long, value_name = "PATH")]
+    output: PathBuf,
+    /// format of output.
+    #[arg(short, long, value_enum, default_value_t = ExportFormat::Csv)]
+    format: ExportFormat,
+    /// encoding of output.
+    #[arg(short, long, value_enum, default_value_t = TextEncoding::Utf8)]
+    encoding: TextEncoding,
+    /// group entries by the first character of sakuraName. only for html and markdown.
+    #[arg(short, long)]
+    group: bool,
+    /// names to write. only for text.
+    #[arg(long, value_enum, default_value_t = NameField::All)]
+    field: NameField,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ExportFormat {
+    /// comma-separated values with a header row.
+    Csv,
+    /// tab-separated values with a header row.
+    Tsv,
+    /// self-contained html page with sortable tables.
+    Html,
+    /// markdown tables.
+    Markdown,
+    /// sqlite database with `ghosts` and `metadata` tables.
+    Sqlite,
+    /// plain text with one name per line.
+    Text,
+    /// typescript module exporting the list with its types.
+    Typescript,
+}
+
+#[derive(clap::Args)]
+#[command(about, long_about = None)]
+pub(crate) struct ImportArgs {
+    /// path to file to import. `-` for stdin.
+    #[arg(short, long, value_name = "PATH")]
+    input: PathBuf,
+    /// output path. `-` for stdout.
+    #[arg(short, long, value_name = "PATH", default_value = DEFAULT_TARGET_PATH)]
+    output: PathBuf,
+    /// format of input.
+    #[arg(short, long, value_enum, default_value_t = ImportFormat::Csv)]
+    format: ImportFormat,
+    /// encoding of input.
+    #[arg(short, long, value_enum, default_value_t = TextEncoding::Utf8)]
+    encoding: TextEncoding,
+    #[command(flatten)]
+    write_options: WriteOptions,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ImportFormat {
+    /// comma-separated values with a header row.
+    Csv,
+    /// tab-separated values with a header row.
+    Tsv,
+    /// sqlite database with `ghosts` table, as written by export.
+    Sqlite,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub(crate) enum TextEncoding {
+    /// UTF-8 without BOM.
+    #[value(name = "utf-8")]
+    Utf8,
+    /// Shift_JIS without BOM.
+    #[value(name = "shift_jis")]
+    ShiftJis,
+    /// EUC-JP without BOM.
+    #[value(name = "euc-jp")]
+    EucJp,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub(crate) enum NameField {
+    /// directory names.
+    Directory,
+    /// sakuraNames.
+    Sakura,
+    /// keroNames, except ghosts without kero or with blank kero.
+    Kero,
+    /// all of them.
+    All,
 }
 
 #[derive(clap::Args)]
 #[command(about, long_about = None)]
 pub(crate) struct MergeArgs {
-    /// paths to ghost data jsons.
+    /// paths to ghost data jsons, directories of them or glob patterns. `-` for stdin.
     #[arg(short, long, value_name = "JSONS")]
     input: Vec<PathBuf>,
-    /// output path.
+    /// take jsons in subdirectories of input directories.
+    #[arg(short, long)]
+    recursive: bool,
+    /// recover well-formed entries from partially invalid inputs.
+    #[arg(long)]
+    lenient: bool,
+    /// output path. `-` for stdout.
     #[arg(short, long, value_name = "PATH", default_value = DEFAULT_TARGET_PATH)]
     output: PathBuf,
+    /// path to the json which inputs started from. merges changes of each input from it.
+    #[arg(long, value_name = "PATH")]
+    base: Option<PathBuf>,
+    /// path to write conflicts as json.
+    #[arg(long, value_name = "PATH")]
+    conflict_report: Option<PathBuf>,
+    /// fail without writing output when conflicts are left unresolved.
+    #[arg(long)]
+    fail_on_conflict: bool,
+    /// how to resolve conflicts among inputs.
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::KeepAll)]
+    policy: ConflictPolicy,
+    #[command(flatten)]
+    write_options: WriteOptions,
+}
+
+#[derive(clap::Args)]
+#[command(about, long_about = None)]
+pub(crate) struct NoteArgs {
+    /// path to target. `-` for stdin and stdout.
+    #[arg(short, long, value_name = "PATH", default_value = DEFAULT_TARGET_PATH)]
+    target: PathBuf,
+    /// directory name of ghost data to annotate.
+    #[arg(short, long, value_name = "DIR")]
+    directory: Option<String>,
+    /// sakuraName of ghost data to annotate.
+    #[arg(short, long, value_name = "NAME")]
+    sakura_name: Option<String>,
+    /// keroName of ghost data to annotate.
+    #[arg(short, long, value_name = "NAME")]
+    kero_name: Option<String>,
+    /// note to set.
+    #[arg(long, value_name = "TEXT", required_unless_present = "clear")]
+    set: Option<String>,
+    /// clear the note.
+    #[arg(short, long, conflicts_with = "set")]
+    clear: bool,
+    #[command(flatten)]
+    write_options: WriteOptions,
+}
+
+#[derive(clap::Args)]
+#[command(about, long_about = None)]
+pub(crate) struct SchemaArgs {
+    /// output path. `-` for stdout.
+    #[arg(short, long, value_name = "PATH", default_value = DEFAULT_SCHEMA_PATH)]
+    output: PathBuf,
+}
+
+#[derive(clap::Args)]
+#[command(about, long_about = None)]
+pub(crate) struct TagArgs {
+    /// path to target. `-` for stdin and stdout.
+    #[arg(short, long, value_name = "PATH", default_value = DEFAULT_TARGET_PATH)]
+    target: PathBuf,
+    /// directory name of ghost data to tag.
+    #[arg(short, long, value_name = "DIR")]
+    directory: Option<String>,
+    /// sakuraName of ghost data to tag.
+    #[arg(short, long, value_name = "NAME")]
+    sakura_name: Option<String>,
+    /// keroName of ghost data to tag.
+    #[arg(short, long, value_name = "NAME")]
+    kero_name: Option<String>,
+    /// tags to add. e.g. retired, test, private.
+    #[arg(short, long, value_name = "TAG")]
+    add: Vec<String>,
+    /// tags to remove.
+    #[arg(short, long, value_name = "TAG")]
+    remove: Vec<String>,
+    /// remove all tags before adding.
+    #[arg(short, long)]
+    clear: bool,
+    #[command(flatten)]
+    write_options: WriteOptions,
+}
+
+#[derive(clap::Args)]
+#[command(about, long_about = None)]
+pub(crate) struct ValidateArgs {
+    /// path to target. `-` for stdin.
+    #[arg(short, long, value_name = "PATH", default_value = DEFAULT_TARGET_PATH)]
+    target: PathBuf,
+}
+
+impl Cli {
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    pub fn global_options(&self) -> &GlobalOptions {
+        &self.global_options
+    }
+}
+
+impl GlobalOptions {
+    #[cfg(test)]
+    pub fn new(key: IdentityKey, normalize: bool) -> GlobalOptions {
+        GlobalOptions { key, normalize }
+    }
+
+    pub fn key(&self) -> IdentityKey {
+        self.key
+    }
+
+    pub fn normalize(&self) -> bool {
+        self.normalize
+    }
 }
 
 impl AppendArgs {
     #[cfg(test)]
-    pub fn new(input: Vec<PathBuf>, output: PathBuf) -> AppendArgs {
-        AppendArgs { input, output }
+    pub fn new(
+        input: Vec<PathBuf>,
+        output: PathBuf,
+        policy: ConflictPolicy,
+        upsert: bool,
+        lenient: bool,
+    ) -> AppendArgs {
+        AppendArgs {
+            input,
+            output,
+            policy,
+            upsert,
+            conflict_report: None,
+            lenient,
+            write_options: WriteOptions::default(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_conflict_report(self, conflict_report: Option<PathBuf>) -> AppendArgs {
+        AppendArgs {
+            conflict_report,
+            ..self
+        }
     }
 
     pub fn input(&self) -> &Vec<PathBuf> {
@@ -79,12 +421,36 @@ impl AppendArgs {
     pub fn output(&self) -> &PathBuf {
         &self.output
     }
+
+    pub fn policy(&self) -> ConflictPolicy {
+        self.policy
+    }
+
+    pub fn upsert(&self) -> bool {
+        self.upsert
+    }
+
+    pub fn conflict_report(&self) -> Option<&PathBuf> {
+        self.conflict_report.as_ref()
+    }
+
+    pub fn lenient(&self) -> bool {
+        self.lenient
+    }
+
+    pub fn write_options(&self) -> &WriteOptions {
+        &self.write_options
+    }
 }
 
 impl BuildArgs {
     #[cfg(test)]
     pub fn new(input: Vec<PathBuf>, output: PathBuf) -> BuildArgs {
-        BuildArgs { input, output }
+        BuildArgs {
+            input,
+            output,
+            write_options: WriteOptions::default(),
+        }
     }
 
     pub fn input(&self) -> &Vec<PathBuf> {
@@ -93,6 +459,10 @@ impl BuildArgs {
     pub fn output(&self) -> &PathBuf {
         &self.output
     }
+
+    pub fn write_options(&self) -> &WriteOptions {
+        &self.write_options
+    }
 }
 
 impl EraseArgs {
@@ -102,12 +472,17 @@ impl EraseArgs {
         directory: Option<String>,
         sakura_name: Option<String>,
         kero_name: Option<String>,
+        tombstone: bool,
+        reason: Option<String>,
     ) -> EraseArgs {
         EraseArgs {
             target,
             directory,
             sakura_name,
             kero_name,
+            tombstone,
+            reason,
+            write_options: WriteOptions::default(),
         }
     }
 
@@ -126,19 +501,321 @@ impl EraseArgs {
     pub fn kero_name(&self) -> Option<&String> {
         self.kero_name.as_ref()
     }
+
+    pub fn tombstone(&self) -> bool {
+        self.tombstone
+    }
+
+    pub fn reason(&self) -> Option<&String> {
+        self.reason.as_ref()
+    }
+
+    pub fn write_options(&self) -> &WriteOptions {
+        &self.write_options
+    }
+}
+
+impl ExportArgs {
+    #[cfg(test)]
+    pub fn new(
+        input: PathBuf,
+        output: PathBuf,
+        format: ExportFormat,
+        encoding: TextEncoding,
+        group: bool,
+        field: NameField,
+    ) -> ExportArgs {
+        ExportArgs {
+            input,
+            output,
+            format,
+            encoding,
+            group,
+            field,
+        }
+    }
+
+    pub fn input(&self) -> &PathBuf {
+        &self.input
+    }
+
+    pub fn output(&self) -> &PathBuf {
+        &self.output
+    }
+
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    pub fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    pub fn group(&self) -> bool {
+        self.group
+    }
+
+    pub fn field(&self) -> NameField {
+        self.field
+    }
+}
+
+impl ImportArgs {
+    #[cfg(test)]
+    pub fn new(
+        input: PathBuf,
+        output: PathBuf,
+        format: ImportFormat,
+        encoding: TextEncoding,
+    ) -> ImportArgs {
+        ImportArgs {
+            input,
+            output,
+            format,
+            encoding,
+            write_options: WriteOptions::default(),
+        }
+    }
+
+    pub fn input(&self) -> &PathBuf {
+        &self.input
+    }
+
+    pub fn output(&self) -> &PathBuf {
+        &self.output
+    }
+
+    pub fn format(&self) -> ImportFormat {
+        self.format
+    }
+
+    pub fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    pub fn write_options(&self) -> &WriteOptions {
+        &self.write_options
+    }
 }
 
 impl MergeArgs {
     #[cfg(test)]
-    pub fn new(input: Vec<PathBuf>, output: PathBuf) -> MergeArgs {
-        MergeArgs { input, output }
+    pub fn new(
+        input: Vec<PathBuf>,
+        recursive: bool,
+        output: PathBuf,
+        base: Option<PathBuf>,
+        conflict_report: Option<PathBuf>,
+        fail_on_conflict: bool,
+        policy: ConflictPolicy,
+    ) -> MergeArgs {
+        MergeArgs {
+            input,
+            recursive,
+            output,
+            base,
+            conflict_report,
+            fail_on_conflict,
+            policy,
+            lenient: false,
+            write_options: WriteOptions::default(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_lenient(self, lenient: bool) -> MergeArgs {
+        MergeArgs { lenient, ..self }
     }
 
     pub fn input(&self) -> &Vec<PathBuf> {
         &self.input
     }
 
+    pub fn recursive(&self) -> bool {
+        self.recursive
+    }
+
+    pub fn lenient(&self) -> bool {
+        self.lenient
+    }
+
     pub fn output(&self) -> &PathBuf {
         &self.output
     }
+
+    pub fn base(&self) -> Option<&PathBuf> {
+        self.base.as_ref()
+    }
+
+    pub fn conflict_report(&self) -> Option<&PathBuf> {
+        self.conflict_report.as_ref()
+    }
+
+    pub fn fail_on_conflict(&self) -> bool {
+        self.fail_on_conflict
+    }
+
+    pub fn policy(&self) -> ConflictPolicy {
+        self.policy
+    }
+
+    pub fn write_options(&self) -> &WriteOptions {
+        &self.write_options
+    }
+}
+
+impl NoteArgs {
+    #[cfg(test)]
+    pub fn new(
+        target: PathBuf,
+        directory: Option<String>,
+        sakura_name: Option<String>,
+        kero_name: Option<String>,
+        set: Option<String>,
+        clear: bool,
+    ) -> NoteArgs {
+        NoteArgs {
+            target,
+            directory,
+            sakura_name,
+            kero_name,
+            set,
+            clear,
+            write_options: WriteOptions::default(),
+        }
+    }
+
+    pub fn target(&self) -> &PathBuf {
+        &self.target
+    }
+
+    pub fn directory(&self) -> Option<&String> {
+        self.directory.as_ref()
+    }
+
+    pub fn sakura_name(&self) -> Option<&String> {
+        self.sakura_name.as_ref()
+    }
+
+    pub fn kero_name(&self) -> Option<&String> {
+        self.kero_name.as_ref()
+    }
+
+    pub fn set(&self) -> Option<&String> {
+        self.set.as_ref()
+    }
+
+    pub fn clear(&self) -> bool {
+        self.clear
+    }
+
+    pub fn write_options(&self) -> &WriteOptions {
+        &self.write_options
+    }
+}
+
+impl SchemaArgs {
+    #[cfg(test)]
+    pub fn new(output: PathBuf) -> SchemaArgs {
+        SchemaArgs { output }
+    }
+
+    pub fn output(&self) -> &PathBuf {
+        &self.output
+    }
+}
+
+impl TagArgs {
+    #[cfg(test)]
+    pub fn new(
+        target: PathBuf,
+        directory: Option<String>,
+        sakura_name: Option<String>,
+        kero_name: Option<String>,
+        add: Vec<String>,
+        remove: Vec<String>,
+        clear: bool,
+    ) -> TagArgs {
+        TagArgs {
+            target,
+            directory,
+            sakura_name,
+            kero_name,
+            add,
+            remove,
+            clear,
+            write_options: WriteOptions::default(),
+        }
+    }
+
+    pub fn target(&self) -> &PathBuf {
+        &self.target
+    }
+
+    pub fn directory(&self) -> Option<&String> {
+        self.directory.as_ref()
+    }
+
+    pub fn sakura_name(&self) -> Option<&String> {
+        self.sakura_name.as_ref()
+    }
+
+    pub fn kero_name(&self) -> Option<&String> {
+        self.kero_name.as_ref()
+    }
+
+    pub fn add(&self) -> &Vec<String> {
+        &self.add
+    }
+
+    pub fn remove(&self) -> &Vec<String> {
+        &self.remove
+    }
+
+    pub fn clear(&self) -> bool {
+        self.clear
+    }
+
+    pub fn write_options(&self) -> &WriteOptions {
+        &self.write_options
+    }
+}
+
+impl ValidateArgs {
+    #[cfg(test)]
+    pub fn new(target: PathBuf) -> ValidateArgs {
+        ValidateArgs { target }
+    }
+
+    pub fn target(&self) -> &PathBuf {
+        &self.target
+    }
+}
+
+impl WriteOptions {
+    #[cfg(test)]
+    pub fn new(style: OutputStyle, list_format: Option<ListFormat>) -> WriteOptions {
+        WriteOptions {
+            style,
+            list_format,
+            backups: 0,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_backups(self, backups: usize) -> WriteOptions {
+        WriteOptions { backups, ..self }
+    }
+
+    pub fn style(&self) -> OutputStyle {
+        self.style
+    }
+
+    pub fn list_format(&self) -> Option<ListFormat> {
+        self.list_format
+    }
+
+    pub fn backups(&self) -> usize {
+        self.backups
+    }
 }
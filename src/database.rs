@@ -0,0 +1,399 @@
+use std::path::Path;
+
+use rusqlite::{types::ValueRef, Connection, OpenFlags, OptionalExtension};
+use serde_json::{Map, Value};
+
+use crate::{
+    ast::{GhostData, GhostJson},
+    io::is_std_stream,
+};
+
+const GHOSTS_TABLE: &str = "ghosts";
+const METADATA_TABLE: &str = "metadata";
+
+const DIRECTORY: &str = "directory";
+const SAKURA_NAME: &str = "sakuraName";
+const KERO_NAME: &str = "keroName";
+const TAGS: &str = "tags";
+const NOTE: &str = "note";
+
+fn sql_error(e: rusqlite::Error) -> std::io::Error {
+    std::io::Error::other(e)
+}
+
+fn open<P>(path: P, flags: OpenFlags) -> Result<Connection, std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    if is_std_stream(&path) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "sqlite database cannot be stdin or stdout",
+        ));
+    }
+
+    Connection::open_with_flags(path, flags).map_err(sql_error)
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// writes ghost data into `ghosts` table, and the other fields of the list into `metadata` table.
+/// other tables in the database are left as they are.
+pub(crate) fn write_database<P>(path: P, json: &GhostJson) -> Result<(), std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let mut extra_keys: Vec<&String> = Vec::new();
+    for k in json.ghost_list().iter().flat_map(|v| v.extra().keys()) {
+        if !extra_keys.contains(&k) {
+            extra_keys.push(k);
+        }
+    }
+    extra_keys.sort();
+
+    let fixed_columns = [DIRECTORY, SAKURA_NAME, KERO_NAME, TAGS, NOTE];
+    if let Some(k) = extra_keys
+        .iter()
+        .find(|k| fixed_columns.iter().any(|c| c.eq_ignore_ascii_case(k)))
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("field `{}` conflicts with a column", k),
+        ));
+    }
+
+    let mut header = serde_json::to_value(json)?;
+    if let Some(v) = header.as_object_mut() {
+        v.remove("ghostList");
+    }
+
+    let mut connection = open(path, OpenFlags::default())?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+
+    transaction
+        .execute_batch(&format!(
+            "DROP TABLE IF EXISTS {ghosts}; DROP TABLE IF EXISTS {metadata};\
+             CREATE TABLE {metadata} (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+            ghosts = GHOSTS_TABLE,
+            metadata = METADATA_TABLE,
+        ))
+        .map_err(sql_error)?;
+
+    let columns: Vec<String> = fixed_columns
+        .iter()
+        .map(|v| v.to_string())
+        .chain(extra_keys.iter().map(|v| v.to_string()))
+        .map(|v| quote(&v))
+        .collect();
+    transaction
+        .execute(
+            &format!(
+                "CREATE TABLE {} ({} TEXT NOT NULL, {} TEXT NOT NULL, {})",
+                GHOSTS_TABLE,
+                columns[0],
+                columns[1],
+                columns[2..].join(", ")
+            ),
+            [],
+        )
+        .map_err(sql_error)?;
+
+    {
+        let mut statement = transaction
+            .prepare(&format!("INSERT INTO {} VALUES (?1, ?2)", METADATA_TABLE))
+            .map_err(sql_error)?;
+        for (k, v) in header.as_object().into_iter().flatten() {
+            statement.execute((k, v.to_string())).map_err(sql_error)?;
+        }
+
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+        let mut statement = transaction
+            .prepare(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                GHOSTS_TABLE,
+                columns.join(", "),
+                placeholders.join(", ")
+            ))
+            .map_err(sql_error)?;
+        for v in json.ghost_list().iter() {
+            let mut values: Vec<rusqlite::types::Value> = vec![
+                v.directory().clone().into(),
+                v.sakura_name().clone().into(),
+                v.kero_name().cloned().into(),
+                Some(v.tags())
+                    .filter(|v| !v.is_empty())
+                    .map(|v| Value::from(v.clone()).to_string())
+                    .into(),
+                v.note().cloned().into(),
+            ];
+            values.extend(extra_keys.iter().map(|k| to_sql_value(v.extra().get(*k))));
+            statement
+                .execute(rusqlite::params_from_iter(values))
+                .map_err(sql_error)?;
+        }
+    }
+
+    transaction.commit().map_err(sql_error)
+}
+
+/// strings and numbers are stored as they are, and the other values as json text.
+fn to_sql_value(value: Option<&Value>) -> rusqlite::types::Value {
+    use rusqlite::types::Value as SqlValue;
+
+    match value {
+        None | Some(Value::Null) => SqlValue::Null,
+        Some(Value::String(v)) => SqlValue::Text(v.clone()),
+        Some(Value::Number(v)) => match (v.as_i64(), v.as_f64()) {
+            (Some(i), _) => SqlValue::Integer(i),
+            (None, Some(f)) => SqlValue::Real(f),
+            (None, None) => SqlValue::Text(v.to_string()),
+        },
+        Some(v) => SqlValue::Text(v.to_string()),
+    }
+}
+
+/// reads ghost data from a database written by `write_database`.
+/// columns other than known ones are kept as unknown fields.
+pub(crate) fn read_database<P>(path: P) -> Result<GhostJson, std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let connection = open(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let mut root = Map::new();
+    let has_metadata = connection
+        .query_row(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [METADATA_TABLE],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(sql_error)?
+        .is_some();
+    if has_metadata {
+        let mut statement = connection
+            .prepare(&format!("SELECT key, value FROM {}", METADATA_TABLE))
+            .map_err(sql_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(sql_error)?;
+        for row in rows {
+            let (k, v) = row.map_err(sql_error)?;
+            root.insert(k, serde_json::from_str(&v)?);
+        }
+    }
+    if !root.contains_key("update") {
+        root.insert(
+            "update".to_string(),
+            serde_json::to_value(GhostJson::new(chrono::Utc::now(), Vec::new()).update())?,
+        );
+    }
+    root.insert("ghostList".to_string(), Value::Array(Vec::new()));
+    let json: GhostJson = serde_json::from_value(Value::Object(root))?;
+
+    let mut statement = connection
+        .prepare(&format!("SELECT * FROM {} ORDER BY rowid", GHOSTS_TABLE))
+        .map_err(sql_error)?;
+    let names: Vec<String> = statement
+        .column_names()
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    let column = |name: &str| names.iter().position(|v| v.eq_ignore_ascii_case(name));
+    let known_columns = [
+        column(DIRECTORY),
+        column(SAKURA_NAME),
+        column(KERO_NAME),
+        column(TAGS),
+        column(NOTE),
+    ];
+    let [Some(directory_column), Some(sakura_name_column), kero_name_column, tags_column, note_column] =
+        known_columns
+    else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("missing column `{}` or `{}`", DIRECTORY, SAKURA_NAME),
+        ));
+    };
+
+    let mut rows = statement.query([]).map_err(sql_error)?;
+    let mut ghost_list = Vec::new();
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        let text = |i: Option<usize>| -> Result<Option<String>, rusqlite::Error> {
+            match i {
+                Some(i) => row.get::<_, Option<String>>(i),
+                None => Ok(None),
+            }
+        };
+
+        let mut v = GhostData::new(
+            text(Some(directory_column))
+                .map_err(sql_error)?
+                .unwrap_or_default(),
+            text(Some(sakura_name_column))
+                .map_err(sql_error)?
+                .unwrap_or_default(),
+            text(kero_name_column).map_err(sql_error)?,
+        );
+        if let Some(tags) = text(tags_column).map_err(sql_error)? {
+            for tag in serde_json::from_str::<Vec<String>>(&tags)? {
+                v.add_tag(&tag);
+            }
+        }
+        v.set_note(text(note_column).map_err(sql_error)?);
+
+        let mut extra = Map::new();
+        for (i, name) in names.iter().enumerate() {
+            if known_columns.contains(&Some(i)) {
+                continue;
+            }
+            let value = match row.get_ref(i).map_err(sql_error)? {
+                ValueRef::Null => continue,
+                ValueRef::Integer(v) => Value::from(v),
+                ValueRef::Real(v) => Value::from(v),
+                ValueRef::Text(v) | ValueRef::Blob(v) => {
+                    Value::String(String::from_utf8_lossy(v).to_string())
+                }
+            };
+            extra.insert(name.clone(), value);
+        }
+
+        ghost_list.push(v.with_extra(extra));
+    }
+
+    Ok(json.with_ghost_list(ghost_list))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod write_database {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        fn sample() -> GhostJson {
+            serde_json::from_str(
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":"","noKero":true,"rank":3},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book","tags":["retired","test"],"note":"note","author":"tukinami"}],"source":"teammate"}"#,
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn round_trip() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.sqlite");
+            let json = sample();
+
+            write_database(&path, &json).unwrap();
+            assert_eq!(read_database(&path).unwrap(), json);
+
+            // writing again replaces the tables.
+            write_database(&path, &json).unwrap();
+            assert_eq!(read_database(&path).unwrap(), json);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn queryable_by_sql() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.sqlite");
+
+            write_database(&path, &sample()).unwrap();
+
+            let connection = Connection::open(&path).unwrap();
+            let result: Vec<(String, Option<String>, Option<String>)> = connection
+                .prepare("SELECT directory, keroName, author FROM ghosts ORDER BY rowid")
+                .unwrap()
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(
+                result,
+                vec![
+                    ("100th_year".to_string(), None, None),
+                    (
+                        "FoxTheory".to_string(),
+                        Some("book".to_string()),
+                        Some("tukinami".to_string())
+                    ),
+                ]
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_field_conflicts_with_column() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.sqlite");
+            let json: GhostJson = serde_json::from_str(
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"a","sakuraName":"a","keroName":"","Directory":"b"}]}"#,
+            )
+            .unwrap();
+
+            assert!(write_database(&path, &json).is_err());
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod read_database {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        #[test]
+        fn success_when_hand_made_table() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.sqlite");
+
+            let connection = Connection::open(&path).unwrap();
+            connection
+                .execute_batch(
+                    "CREATE TABLE ghosts (sakuraName TEXT, directory TEXT, score REAL);\
+                     INSERT INTO ghosts VALUES ('霊', '100th_year', 1.5);",
+                )
+                .unwrap();
+
+            let result = read_database(&path).unwrap();
+            let mut extra = Map::new();
+            extra.insert("score".to_string(), Value::from(1.5));
+            assert_eq!(
+                result.ghost_list(),
+                &vec![
+                    GhostData::new("100th_year".to_string(), "霊".to_string(), None)
+                        .with_extra(extra)
+                ]
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_std_stream() {
+            assert!(read_database("-").is_err());
+        }
+
+        #[test]
+        fn failed_when_no_ghosts_table() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.sqlite");
+            Connection::open(&path)
+                .unwrap()
+                .execute_batch("CREATE TABLE others (a TEXT);")
+                .unwrap();
+
+            assert!(read_database(&path).is_err());
+
+            out_dir.close().unwrap();
+        }
+    }
+}
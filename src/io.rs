@@ -1,12 +1,19 @@
 use std::{
     fs::File,
     io::{Read, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
+use chrono::Utc;
 use encoding_rs::{EUC_JP, ISO_2022_JP, SHIFT_JIS, UTF_8};
+use flate2::{read::GzDecoder, write::GzEncoder};
+use serde_json::Value;
 
-use crate::ast::GhostJson;
+use crate::{
+    ast::{GhostData, GhostJson},
+    config::{ListFormat, OutputStyle, TextEncoding, WriteOptions},
+    validation::{salvage_ndjson_str, salvage_str, Problem},
+};
 
 pub(crate) fn load_setting_file<P>(path: P) -> Result<String, std::io::Error>
 where
@@ -43,43 +50,546 @@ where
     }
 }
 
+/// whether `path` is `-`, which stands for stdin or stdout.
+pub(crate) fn is_std_stream<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    path.as_ref() == Path::new("-")
+}
+
+fn read_bytes<P>(path: P) -> Result<Vec<u8>, std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let mut buffer = Vec::new();
+    if is_std_stream(&path) {
+        std::io::stdin().lock().read_to_end(&mut buffer)?;
+    } else {
+        File::open(path)?.read_to_end(&mut buffer)?;
+    }
+
+    Ok(buffer)
+}
+
+/// loads utf-8 text. gzip or zstd compressed contents are decompressed by their magic bytes.
+pub(crate) fn load_text<P>(path: P) -> Result<String, std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let buffer_raw = read_bytes(path)?;
+
+    let buffer = if buffer_raw.starts_with(GZIP_MAGIC) {
+        let mut buffer = Vec::new();
+        GzDecoder::new(buffer_raw.as_slice()).read_to_end(&mut buffer)?;
+        buffer
+    } else if buffer_raw.starts_with(ZSTD_MAGIC) {
+        zstd::decode_all(buffer_raw.as_slice())?
+    } else {
+        buffer_raw
+    };
+
+    String::from_utf8(buffer).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+/// compression chosen by the extension of `path`, and the file name without it.
+fn compression_of<P>(path: P) -> (Option<Compression>, String)
+where
+    P: AsRef<Path>,
+{
+    let file_name = path
+        .as_ref()
+        .file_name()
+        .map(|v| v.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if let Some(v) = file_name.strip_suffix(".gz") {
+        (Some(Compression::Gzip), v.to_string())
+    } else if let Some(v) = file_name.strip_suffix(".zst") {
+        (Some(Compression::Zstd), v.to_string())
+    } else {
+        (None, file_name)
+    }
+}
+
+/// loads a ghost data list. NDJSON is chosen by `.ndjson` or `.jsonl` extension,
+/// and sniffed from contents when the extension is neither of them nor `.json`.
 pub(crate) fn load_json<P>(path: P) -> Result<GhostJson, std::io::Error>
 where
     P: AsRef<Path>,
 {
-    let mut fs = File::open(path)?;
-    let mut contents = String::new();
+    let format = list_format_of(&path);
+    let contents = load_text(path)?;
 
-    fs.read_to_string(&mut contents)?;
+    match format {
+        Some(ListFormat::Json) => parse_json(&contents),
+        Some(ListFormat::Ndjson) => parse_ndjson(&contents),
+        None => parse_json(&contents).or_else(|e| {
+            if may_be_ndjson(&contents) {
+                parse_ndjson(&contents)
+            } else {
+                Err(e)
+            }
+        }),
+    }
+}
 
-    serde_json::from_str::<GhostJson>(&contents).map_err(|e| {
-        if let Some(error_kind) = e.io_error_kind() {
-            std::io::Error::from(error_kind)
-        } else {
-            std::io::Error::from(std::io::ErrorKind::InvalidData)
+/// loads a ghost data list leniently, recovering well-formed entries of a damaged list.
+/// problems of what was left out are returned with it.
+pub(crate) fn salvage_json<P>(path: P) -> Result<(GhostJson, Vec<Problem>), std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let format = list_format_of(&path);
+    let contents = load_text(path)?;
+
+    Ok(match format {
+        Some(ListFormat::Json) => salvage_str(&contents),
+        Some(ListFormat::Ndjson) => salvage_ndjson_str(&contents),
+        None if may_be_ndjson(&contents) => {
+            match (parse_json(&contents), parse_ndjson(&contents)) {
+                (Ok(v), _) | (_, Ok(v)) => (v, Vec::new()),
+                _ => salvage_str(&contents),
+            }
         }
+        None => salvage_str(&contents),
     })
 }
 
-pub(crate) fn write_json<P>(path: P, json: &GhostJson) -> Result<(), std::io::Error>
+/// whether `contents` may be NDJSON when the extension does not tell:
+/// more than one line, and the first one is a json object by itself.
+/// a damaged single json object is never taken for NDJSON.
+pub(crate) fn may_be_ndjson(contents: &str) -> bool {
+    let mut lines = contents.lines().filter(|v| !v.trim().is_empty());
+    match (lines.next(), lines.next()) {
+        (Some(first), Some(_)) => serde_json::from_str::<Value>(first).is_ok_and(|v| v.is_object()),
+        _ => false,
+    }
+}
+
+/// list format chosen by the extension of `path`, if any.
+pub(crate) fn list_format_of<P>(path: P) -> Option<ListFormat>
 where
     P: AsRef<Path>,
 {
-    let contents = serde_json::to_string(json).map_err(|e| {
-        if let Some(error_kind) = e.io_error_kind() {
-            std::io::Error::from(error_kind)
+    let (_, file_name) = compression_of(path);
+
+    if file_name.ends_with(".ndjson") || file_name.ends_with(".jsonl") {
+        Some(ListFormat::Ndjson)
+    } else if file_name.ends_with(".json") {
+        Some(ListFormat::Json)
+    } else {
+        None
+    }
+}
+
+/// files named by `paths` in a stable order, without duplicates.
+/// a directory gives ghost data lists in it, sorted by path, and those in subdirectories
+/// when `recursive`. a glob pattern gives matching paths sorted by path.
+/// `exclude` is left out of what directories and patterns give, but not of explicit paths.
+pub(crate) fn list_files(
+    paths: &[PathBuf],
+    recursive: bool,
+    exclude: Option<&Path>,
+) -> Result<Vec<PathBuf>, std::io::Error> {
+    let exclude = exclude.and_then(|v| v.canonicalize().ok());
+    let exclude = exclude.as_deref();
+
+    let mut files = Vec::new();
+    for p in paths.iter() {
+        if is_std_stream(p) || p.exists() || !is_pattern(p) {
+            push_files(p, recursive, true, exclude, &mut files)?;
+            continue;
+        }
+
+        let matches = glob::glob(&p.to_string_lossy())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        for m in matches {
+            let m = m.map_err(std::io::Error::from)?;
+            if !is_excluded(&m, exclude) {
+                push_files(&m, recursive, true, exclude, &mut files)?;
+            }
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|v| seen.insert(v.clone()));
+    Ok(files)
+}
+
+fn is_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+fn is_excluded(path: &Path, exclude: Option<&Path>) -> bool {
+    exclude.is_some() && path.canonicalize().ok().as_deref() == exclude
+}
+
+/// pushes `path`, or lists in it when it is a directory. nested directories are taken
+/// only when `recursive`, and other files only when `explicit`.
+fn push_files(
+    path: &Path,
+    recursive: bool,
+    explicit: bool,
+    exclude: Option<&Path>,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), std::io::Error> {
+    if !path.is_dir() {
+        if explicit || list_format_of(path).is_some() {
+            files.push(path.to_path_buf());
+        }
+        return Ok(());
+    }
+    if !explicit && !recursive {
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?
+        .map(|v| v.map(|v| v.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for v in entries.iter().filter(|v| !is_excluded(v, exclude)) {
+        push_files(v, recursive, false, exclude, files)?;
+    }
+    Ok(())
+}
+
+fn json_error(e: serde_json::Error) -> std::io::Error {
+    if let Some(error_kind) = e.io_error_kind() {
+        std::io::Error::from(error_kind)
+    } else {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    }
+}
+
+fn parse_json(contents: &str) -> Result<GhostJson, std::io::Error> {
+    serde_json::from_str::<GhostJson>(contents).map_err(json_error)
+}
+
+/// parses newline-delimited json. a line without `directory` is the header.
+/// without header, `update` is left blank, that is unknown.
+fn parse_ndjson(contents: &str) -> Result<GhostJson, std::io::Error> {
+    let mut header = None;
+    let mut ghost_list = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let line_error = |e: serde_json::Error| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("line {}: {}", i + 1, e),
+            )
+        };
+        let mut value: Value = serde_json::from_str(line).map_err(line_error)?;
+
+        if value.get("directory").is_some() {
+            ghost_list.push(serde_json::from_value::<GhostData>(value).map_err(line_error)?);
+        } else if header.is_none() {
+            if let Some(v) = value.as_object_mut() {
+                v.entry("ghostList").or_insert(Value::Array(Vec::new()));
+            }
+            header = Some(serde_json::from_value::<GhostJson>(value).map_err(line_error)?);
         } else {
-            std::io::Error::from(std::io::ErrorKind::InvalidData)
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("line {}: second header", i + 1),
+            ));
         }
-    })?;
+    }
 
-    let mut fs = File::create(path)?;
-    fs.write_all(contents.as_bytes())?;
-    fs.flush()?;
+    // without header, `update` is left blank, that is unknown.
+    let header =
+        header.unwrap_or_else(|| GhostJson::new(Utc::now(), Vec::new()).with_update(String::new()));
+    let ghost_list = header
+        .ghost_list()
+        .iter()
+        .cloned()
+        .chain(ghost_list)
+        .collect();
+
+    Ok(header.with_ghost_list(ghost_list))
+}
+
+pub(crate) fn write_json<P>(
+    path: P,
+    json: &GhostJson,
+    options: &WriteOptions,
+) -> Result<(), std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let format = options
+        .list_format()
+        .or(list_format_of(&path))
+        .unwrap_or(ListFormat::Json);
+
+    let contents = match (format, options.style()) {
+        (ListFormat::Json, OutputStyle::Compact) => serde_json::to_string(json),
+        (ListFormat::Json, OutputStyle::Pretty) => serde_json::to_string_pretty(json),
+        (ListFormat::Json, OutputStyle::Canonical) => to_canonical_string(json),
+        (ListFormat::Ndjson, style) => to_ndjson_string(json, style == OutputStyle::Canonical),
+    }
+    .map_err(json_error)?;
+
+    rotate_backups(path.as_ref(), options.backups())?;
+
+    write_text(path, &contents)
+}
+
+/// keeps `count` previous versions of `path`, as `path.1` (newest) to `path.{count}`.
+fn rotate_backups(path: &Path, count: usize) -> Result<(), std::io::Error> {
+    if count == 0 || is_std_stream(path) || !path.is_file() {
+        return Ok(());
+    }
+
+    let backup_path = |n: usize| {
+        let mut v = path.as_os_str().to_owned();
+        v.push(format!(".{}", n));
+        PathBuf::from(v)
+    };
+
+    for n in (1..count).rev() {
+        let from = backup_path(n);
+        if from.is_file() {
+            std::fs::rename(&from, backup_path(n + 1))?;
+        }
+    }
+    std::fs::copy(path, backup_path(1))?;
 
     Ok(())
 }
 
+/// writes through a temporary file beside `path`, then renames it to `path`,
+/// so that `path` is never left partially written. writes to stdout when `path` is `-`.
+fn write_atomically<P, F>(path: P, write: F) -> Result<(), std::io::Error>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut dyn Write) -> Result<(), std::io::Error>,
+{
+    let path = path.as_ref();
+    if is_std_stream(path) {
+        return write(&mut std::io::stdout().lock());
+    }
+
+    // devices and pipes cannot be replaced by renaming.
+    if std::fs::metadata(path).is_ok_and(|v| !v.is_file()) {
+        return write(&mut std::fs::OpenOptions::new().write(true).open(path)?);
+    }
+
+    // renaming replaces a symbolic link itself, so write where it points to.
+    let path = if path.is_symlink() {
+        std::fs::canonicalize(path)?
+    } else {
+        path.to_path_buf()
+    };
+
+    let file_name = path
+        .file_name()
+        .ok_or(std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    // a new file has default permissions, so take over those of the replaced one.
+    let permissions = std::fs::metadata(&path).ok().map(|v| v.permissions());
+
+    let result = File::create(&temp_path)
+        .and_then(|mut fs| {
+            if let Some(permissions) = permissions {
+                fs.set_permissions(permissions)?;
+            }
+            write(&mut fs)?;
+            fs.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp_path, &path));
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+fn sort_key(v: &GhostData) -> (&String, &String, Option<&String>) {
+    (v.directory(), v.sakura_name(), v.kero_name())
+}
+
+/// a header line without `ghostList`, then one ghost data per line.
+fn to_ndjson_string(json: &GhostJson, sorted: bool) -> Result<String, serde_json::Error> {
+    let mut header = serde_json::to_value(json)?;
+    if let Some(v) = header.as_object_mut() {
+        v.remove("ghostList");
+    }
+
+    let mut ghost_list: Vec<_> = json.ghost_list().iter().collect();
+    if sorted {
+        ghost_list.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+    }
+
+    let mut contents = serde_json::to_string(&header)?;
+    contents.push('\n');
+    for v in ghost_list {
+        contents.push_str(&serde_json::to_string(v)?);
+        contents.push('\n');
+    }
+
+    Ok(contents)
+}
+
+/// json with sorted entries one per line, so that a change of an entry is a change of a line.
+fn to_canonical_string(json: &GhostJson) -> Result<String, serde_json::Error> {
+    let mut ghost_list: Vec<_> = json.ghost_list().iter().collect();
+    ghost_list.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+    let mut tombstones: Vec<_> = json.tombstones().iter().collect();
+    tombstones.sort_by(|a, b| sort_key(a.ghost()).cmp(&sort_key(b.ghost())));
+
+    let mut fields = vec![format!(
+        "  \"update\": {}",
+        serde_json::to_string(json.update())?
+    )];
+    fields.push(canonical_array("ghostList", &ghost_list)?);
+    if !tombstones.is_empty() {
+        fields.push(canonical_array("tombstones", &tombstones)?);
+    }
+    for (k, v) in json.extra().iter() {
+        fields.push(format!(
+            "  {}: {}",
+            serde_json::to_string(k)?,
+            serde_json::to_string(v)?
+        ));
+    }
+
+    Ok(format!("{{\n{}\n}}\n", fields.join(",\n")))
+}
+
+fn canonical_array<T>(key: &str, values: &[T]) -> Result<String, serde_json::Error>
+where
+    T: serde::Serialize,
+{
+    if values.is_empty() {
+        return Ok(format!("  \"{}\": []", key));
+    }
+
+    let lines = values
+        .iter()
+        .map(|v| serde_json::to_string(v).map(|v| format!("    {}", v)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(format!("  \"{}\": [\n{}\n  ]", key, lines.join(",\n")))
+}
+
+/// writes utf-8 text, compressed when `path` ends with `.gz` or `.zst`.
+pub(crate) fn write_text<P>(path: P, contents: &str) -> Result<(), std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let (compression, _) = compression_of(&path);
+
+    write_atomically(path, |fs| {
+        match compression {
+            None => fs.write_all(contents.as_bytes())?,
+            Some(Compression::Gzip) => {
+                let mut encoder = GzEncoder::new(&mut *fs, flate2::Compression::default());
+                encoder.write_all(contents.as_bytes())?;
+                encoder.finish()?;
+            }
+            Some(Compression::Zstd) => zstd::stream::copy_encode(contents.as_bytes(), &mut *fs, 0)?,
+        }
+        fs.flush()
+    })
+}
+
+fn encoding_of(encoding: TextEncoding) -> &'static encoding_rs::Encoding {
+    match encoding {
+        TextEncoding::Utf8 => UTF_8,
+        TextEncoding::ShiftJis => SHIFT_JIS,
+        TextEncoding::EucJp => EUC_JP,
+    }
+}
+
+/// name of `encoding` to declare in documents, e.g. `Shift_JIS`.
+pub(crate) fn charset_of(encoding: TextEncoding) -> &'static str {
+    encoding_of(encoding).name()
+}
+
+/// loads text in `encoding`. a BOM, if any, takes precedence.
+pub(crate) fn load_encoded_text<P>(
+    path: P,
+    encoding: TextEncoding,
+) -> Result<String, std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let buffer_raw = read_bytes(path)?;
+
+    let (cow, _encoding_used, had_errors) = encoding_of(encoding).decode(&buffer_raw);
+
+    if had_errors {
+        Err(std::io::Error::from(std::io::ErrorKind::InvalidData))
+    } else {
+        Ok(cow.to_string())
+    }
+}
+
+/// writes text in `encoding` without BOM.
+/// fails when `contents` has characters which `encoding` cannot represent.
+pub(crate) fn write_encoded_text<P>(
+    path: P,
+    contents: &str,
+    encoding: TextEncoding,
+) -> Result<(), std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let unmappable = unmappable_chars(contents, encoding);
+    if !unmappable.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "characters not representable in {}: {}",
+                encoding_of(encoding).name(),
+                unmappable.iter().collect::<String>()
+            ),
+        ));
+    }
+
+    let (bytes, _encoding_used, _had_errors) = encoding_of(encoding).encode(contents);
+
+    write_atomically(path, |fs| {
+        fs.write_all(&bytes)?;
+        fs.flush()
+    })
+}
+
+/// characters in `contents` which `encoding` cannot represent, without duplicates.
+pub(crate) fn unmappable_chars(contents: &str, encoding: TextEncoding) -> Vec<char> {
+    let encoding = encoding_of(encoding);
+    let mut result = Vec::new();
+    let mut buf = [0; 4];
+    for c in contents.chars() {
+        let (_, _, had_errors) = encoding.encode(c.encode_utf8(&mut buf));
+        if had_errors && !result.contains(&c) {
+            result.push(c);
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,22 +652,18 @@ mod tests {
             assert_eq!(
                 result.ghost_list(),
                 &vec![
-                    GhostData::new("100th_year".to_string(), "霊".to_string(), "".to_string(),),
+                    GhostData::new("100th_year".to_string(), "霊".to_string(), None,),
                     GhostData::new(
                         "FoxTheory".to_string(),
                         "リサ".to_string(),
-                        "book".to_string(),
+                        Some("book".to_string()),
                     ),
                     GhostData::new(
                         "tanumki".to_string(),
                         "きつね".to_string(),
-                        "たぬき".to_string(),
-                    ),
-                    GhostData::new(
-                        "tcidelam".to_string(),
-                        "シデラム".to_string(),
-                        "".to_string(),
+                        Some("たぬき".to_string()),
                     ),
+                    GhostData::new("tcidelam".to_string(), "シデラム".to_string(), None,),
                 ]
             );
         }
@@ -183,6 +689,31 @@ mod tests {
 
         use super::*;
 
+        #[test]
+        fn keeping_backups_when_option_is_set() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.json");
+            let options = WriteOptions::default().with_backups(1);
+
+            let first = GhostJson::new(Utc::now(), Vec::new());
+            write_json(&path, &first, &options).unwrap();
+            assert!(!out_dir.path().join("test.json.1").exists());
+
+            let second = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("a".to_string(), "a".to_string(), None)],
+            );
+            write_json(&path, &second, &options).unwrap();
+
+            assert_eq!(load_json(&path).unwrap(), second);
+            assert_eq!(
+                load_json(out_dir.path().join("test.json.1")).unwrap(),
+                first
+            );
+
+            out_dir.close().unwrap();
+        }
+
         #[test]
         fn success_when_valid_path() {
             let out_dir = tempdir().unwrap();
@@ -194,26 +725,30 @@ mod tests {
             let json = GhostJson::new(
                 update,
                 vec![
-                    GhostData::new("100th_year".to_string(), "霊".to_string(), "".to_string()),
+                    GhostData::new(
+                        "100th_year".to_string(),
+                        "霊".to_string(),
+                        Some("".to_string()),
+                    ),
                     GhostData::new(
                         "FoxTheory".to_string(),
                         "リサ".to_string(),
-                        "book".to_string(),
+                        Some("book".to_string()),
                     ),
                     GhostData::new(
                         "tanumki".to_string(),
                         "きつね".to_string(),
-                        "たぬき".to_string(),
+                        Some("たぬき".to_string()),
                     ),
                     GhostData::new(
                         "tcidelam".to_string(),
                         "シデラム".to_string(),
-                        "".to_string(),
+                        Some("".to_string()),
                     ),
                 ],
             );
 
-            write_json(&path, &json).unwrap();
+            write_json(&path, &json, &WriteOptions::default()).unwrap();
 
             let mut fs = File::open(path).unwrap();
             let mut buf = String::new();
@@ -221,10 +756,494 @@ mod tests {
             fs.read_to_string(&mut buf).unwrap();
             assert_eq!(
                 buf,
-                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":""},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book"},{"directory":"tanumki","sakuraName":"きつね","keroName":"たぬき"},{"directory":"tcidelam","sakuraName":"シデラム","keroName":""}]}"#
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":"","blankKero":true},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book"},{"directory":"tanumki","sakuraName":"きつね","keroName":"たぬき"},{"directory":"tcidelam","sakuraName":"シデラム","keroName":"","blankKero":true}]}"#
             );
 
             out_dir.close().unwrap();
         }
+
+        #[test]
+        fn pretty_when_pretty_style() {
+            let out_dir = tempdir().unwrap();
+
+            let path = out_dir.path().join("test.json");
+            let json: GhostJson = serde_json::from_str(
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"a","sakuraName":"a","keroName":""}]}"#,
+            )
+            .unwrap();
+
+            write_json(&path, &json, &WriteOptions::new(OutputStyle::Pretty, None)).unwrap();
+
+            let buf = load_text(&path).unwrap();
+            assert_eq!(buf, serde_json::to_string_pretty(&json).unwrap());
+            assert_eq!(load_json(&path).unwrap(), json);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn sorted_line_per_entry_when_canonical_style() {
+            let out_dir = tempdir().unwrap();
+
+            let path = out_dir.path().join("test.json");
+            let json: GhostJson = serde_json::from_str(
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"b","sakuraName":"b","keroName":"","blankKero":true},{"directory":"a","sakuraName":"a","keroName":"","noKero":true}],"source":"teammate"}"#,
+            )
+            .unwrap();
+
+            write_json(
+                &path,
+                &json,
+                &WriteOptions::new(OutputStyle::Canonical, None),
+            )
+            .unwrap();
+
+            let buf = load_text(&path).unwrap();
+            assert_eq!(
+                buf,
+                r#"{
+  "update": "2023-12-18T07:18:31.808Z",
+  "ghostList": [
+    {"directory":"a","sakuraName":"a","keroName":"","noKero":true},
+    {"directory":"b","sakuraName":"b","keroName":"","blankKero":true}
+  ],
+  "source": "teammate"
+}
+"#
+            );
+            let result = load_json(&path).unwrap();
+            assert_eq!(result.ghost_list().len(), 2);
+            assert_eq!(result.extra(), json.extra());
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod encoded_text {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        #[test]
+        fn round_trip_in_shift_jis() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.csv");
+
+            write_encoded_text(&path, "テストsakura,霊\n", TextEncoding::ShiftJis).unwrap();
+
+            let mut buf = Vec::new();
+            File::open(&path).unwrap().read_to_end(&mut buf).unwrap();
+            assert_eq!(&buf[..2], &[0x83, 0x65]);
+
+            let result = load_encoded_text(&path, TextEncoding::ShiftJis).unwrap();
+            assert_eq!(result, "テストsakura,霊\n");
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_unmappable_chars() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.csv");
+
+            let result = write_encoded_text(&path, "ok,😀,한\n", TextEncoding::ShiftJis);
+            assert!(result.is_err());
+            assert_eq!(
+                unmappable_chars("ok,😀,한,😀", TextEncoding::ShiftJis),
+                vec!['😀', '한']
+            );
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod ndjson {
+        use std::path::PathBuf;
+
+        use tempfile::tempdir;
+
+        use crate::ast::Tombstone;
+
+        use super::*;
+
+        fn sample() -> GhostJson {
+            let json: GhostJson = serde_json::from_str(
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"b","sakuraName":"b","keroName":"","blankKero":true},{"directory":"a","sakuraName":"a","keroName":"","noKero":true}],"source":"teammate"}"#,
+            )
+            .unwrap();
+            let tombstone = Tombstone::new(&json.ghost_list()[0], Utc::now(), None);
+            json.with_tombstones(vec![tombstone])
+        }
+
+        #[test]
+        fn round_trip_when_ndjson_extension() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.ndjson");
+            let json = sample();
+
+            write_json(&path, &json, &WriteOptions::default()).unwrap();
+
+            let buf = load_text(&path).unwrap();
+            let lines: Vec<_> = buf.lines().collect();
+            assert_eq!(lines.len(), 3);
+            assert!(lines[0].starts_with(r#"{"source":"teammate","tombstones":[{"#));
+            assert_eq!(
+                lines[1],
+                r#"{"directory":"b","sakuraName":"b","keroName":"","blankKero":true}"#
+            );
+
+            assert_eq!(load_json(&path).unwrap(), json);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn sniffing_when_no_extension() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test");
+            let json = sample();
+
+            let options = WriteOptions::new(OutputStyle::Canonical, Some(ListFormat::Ndjson));
+            write_json(&path, &json, &options).unwrap();
+
+            let result = load_json(&path).unwrap();
+            assert_eq!(result.ghost_list()[0].directory(), "a");
+            assert_eq!(result.ghost_list()[1].directory(), "b");
+            assert_eq!(result.update(), json.update());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn success_when_no_header() {
+            let result = parse_ndjson(
+                "{\"directory\":\"a\",\"sakuraName\":\"a\",\"keroName\":\"\"}\n\n{\"directory\":\"b\",\"sakuraName\":\"b\",\"keroName\":\"\"}\n",
+            )
+            .unwrap();
+            assert_eq!(result.ghost_list().len(), 2);
+            assert_eq!(result.update(), "");
+            assert!(result.update_time().is_none());
+        }
+
+        #[test]
+        fn keeping_ghost_list_in_header() {
+            let result = parse_ndjson(
+                "{\"update\":\"2023-12-18T07:18:31.808Z\",\"ghostList\":[{\"directory\":\"a\",\"sakuraName\":\"a\",\"keroName\":\"\"}]}\n{\"directory\":\"b\",\"sakuraName\":\"b\",\"keroName\":\"\"}\n",
+            )
+            .unwrap();
+            assert_eq!(result.ghost_list().len(), 2);
+
+            let result =
+                parse_ndjson("{\"update\":\"2023-12-18T07:18:31.808Z\",\"ghostList\":5}\n");
+            assert!(result.is_err());
+        }
+
+        #[test]
+        fn not_sniffing_when_single_object() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test");
+
+            std::fs::write(
+                &path,
+                "{\"update\":\"2023-12-18T07:18:31.808Z\",\"ghostList\":5}\n",
+            )
+            .unwrap();
+            assert!(load_json(&path).is_err());
+            std::fs::write(&path, "{\"update\":\"2023-12-18T07:18:31.808Z\"}").unwrap();
+            assert!(load_json(&path).is_err());
+
+            let (result, problems) = salvage_json(&path).unwrap();
+            assert!(result.ghost_list().is_empty());
+            assert!(!problems.is_empty());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_invalid_line() {
+            let result = parse_ndjson(
+                "{\"update\":\"2023-12-18T07:18:31.808Z\"}\n{\"directory\":\"a\",\"sakuraName\":1}\n",
+            );
+            assert!(result.unwrap_err().to_string().starts_with("line 2:"));
+
+            let result = parse_ndjson(
+                "{\"update\":\"2023-12-18T07:18:31.808Z\"}\n{\"update\":\"2023-12-18T07:18:31.808Z\"}\n",
+            );
+            assert!(result.is_err());
+        }
+
+        #[test]
+        fn not_sniffing_when_json_extension() {
+            let base_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/json/invalid/");
+            assert!(load_json(base_path.join("no-ghostList.json")).is_err());
+        }
+    }
+
+    mod compression {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        fn sample() -> GhostJson {
+            serde_json::from_str(
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"a","sakuraName":"a","keroName":"k"}]}"#,
+            )
+            .unwrap()
+        }
+
+        fn magic_of(path: &Path) -> Vec<u8> {
+            let mut buf = Vec::new();
+            File::open(path).unwrap().read_to_end(&mut buf).unwrap();
+            buf[..4].to_vec()
+        }
+
+        #[test]
+        fn round_trip_when_gzip_extension() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.json.gz");
+            let json = sample();
+
+            write_json(&path, &json, &WriteOptions::default()).unwrap();
+
+            assert!(magic_of(&path).starts_with(GZIP_MAGIC));
+            assert_eq!(load_json(&path).unwrap(), json);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn round_trip_when_zstd_ndjson() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.ndjson.zst");
+            let json = sample();
+
+            write_json(&path, &json, &WriteOptions::default()).unwrap();
+
+            assert!(magic_of(&path).starts_with(ZSTD_MAGIC));
+            assert_eq!(load_text(&path).unwrap().lines().count(), 2);
+            assert_eq!(load_json(&path).unwrap(), json);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn detecting_by_magic_bytes() {
+            let out_dir = tempdir().unwrap();
+            let compressed_path = out_dir.path().join("test.json.gz");
+            let path = out_dir.path().join("test.json");
+            let json = sample();
+
+            write_json(&compressed_path, &json, &WriteOptions::default()).unwrap();
+            std::fs::rename(&compressed_path, &path).unwrap();
+
+            assert_eq!(load_json(&path).unwrap(), json);
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod list_files {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        #[test]
+        fn expanding_directories_and_patterns() {
+            let dir = tempdir().unwrap();
+            let lists = dir.path().join("lists");
+            std::fs::create_dir_all(lists.join("sub")).unwrap();
+            for name in ["b.json", "a.ndjson.gz", "readme.md", "sub/c.json"] {
+                std::fs::write(lists.join(name), "").unwrap();
+            }
+
+            let result = list_files(std::slice::from_ref(&lists), false, None).unwrap();
+            assert_eq!(
+                result,
+                vec![lists.join("a.ndjson.gz"), lists.join("b.json")]
+            );
+
+            let result = list_files(std::slice::from_ref(&lists), true, None).unwrap();
+            assert_eq!(
+                result,
+                vec![
+                    lists.join("a.ndjson.gz"),
+                    lists.join("b.json"),
+                    lists.join("sub/c.json")
+                ]
+            );
+
+            let result = list_files(
+                &[
+                    lists.join("readme.md"),
+                    lists.join("*.json"),
+                    lists.join("sub"),
+                ],
+                false,
+                None,
+            )
+            .unwrap();
+            assert_eq!(
+                result,
+                vec![
+                    lists.join("readme.md"),
+                    lists.join("b.json"),
+                    lists.join("sub/c.json")
+                ]
+            );
+
+            let result = list_files(
+                &[lists.join("*/*.json"), lists.join("sub/c.json")],
+                false,
+                None,
+            )
+            .unwrap();
+            assert_eq!(result, vec![lists.join("sub/c.json")]);
+
+            dir.close().unwrap();
+        }
+
+        #[test]
+        fn excluding_only_from_directories_and_patterns() {
+            let dir = tempdir().unwrap();
+            let lists = dir.path().join("lists");
+            std::fs::create_dir_all(&lists).unwrap();
+            for name in ["a.json", "b.json"] {
+                std::fs::write(lists.join(name), "").unwrap();
+            }
+            let output = lists.join("a.json");
+
+            let result = list_files(std::slice::from_ref(&lists), false, Some(&output)).unwrap();
+            assert_eq!(result, vec![lists.join("b.json")]);
+
+            let result = list_files(&[lists.join("*.json")], false, Some(&output)).unwrap();
+            assert_eq!(result, vec![lists.join("b.json")]);
+
+            let result =
+                list_files(&[output.clone(), lists.clone()], false, Some(&output)).unwrap();
+            assert_eq!(result, vec![output.clone(), lists.join("b.json")]);
+
+            dir.close().unwrap();
+        }
+
+        #[test]
+        fn keeping_missing_and_std_stream() {
+            let paths = vec![PathBuf::from("-"), PathBuf::from("missing.json")];
+            assert_eq!(list_files(&paths, true, None).unwrap(), paths);
+        }
+    }
+
+    mod is_std_stream {
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            assert!(is_std_stream("-"));
+            assert!(!is_std_stream("./-"));
+            assert!(!is_std_stream("ghost_list.json"));
+        }
+    }
+
+    mod rotate_backups {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        #[test]
+        fn keeping_newest_backups() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.json");
+            let backup = |n: usize| out_dir.path().join(format!("test.json.{}", n));
+
+            for contents in ["a", "b", "c", "d"] {
+                rotate_backups(&path, 2).unwrap();
+                write_text(&path, contents).unwrap();
+            }
+
+            assert_eq!(load_text(&path).unwrap(), "d");
+            assert_eq!(load_text(backup(1)).unwrap(), "c");
+            assert_eq!(load_text(backup(2)).unwrap(), "b");
+            assert!(!backup(3).exists());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn nothing_when_std_stream() {
+            rotate_backups(Path::new("-"), 2).unwrap();
+        }
+
+        #[test]
+        fn nothing_when_zero() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.json");
+            write_text(&path, "a").unwrap();
+
+            rotate_backups(&path, 0).unwrap();
+
+            assert!(!out_dir.path().join("test.json.1").exists());
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod write_atomically {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        #[cfg(unix)]
+        #[test]
+        fn keeping_symbolic_link() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.json");
+            let link_path = out_dir.path().join("link.json");
+            write_text(&path, "original").unwrap();
+            std::os::unix::fs::symlink(&path, &link_path).unwrap();
+
+            write_text(&link_path, "updated").unwrap();
+
+            assert!(link_path.is_symlink());
+            assert_eq!(load_text(&path).unwrap(), "updated");
+
+            out_dir.close().unwrap();
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn keeping_permissions() {
+            use std::os::unix::fs::PermissionsExt;
+
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.json");
+            write_text(&path, "original").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+            write_text(&path, "updated").unwrap();
+
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+            assert_eq!(load_text(&path).unwrap(), "updated");
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn keeping_original_when_failed() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("test.json");
+            write_text(&path, "original").unwrap();
+
+            let result = write_atomically(&path, |fs| {
+                fs.write_all(b"broken")?;
+                Err(std::io::Error::from(std::io::ErrorKind::StorageFull))
+            });
+
+            assert!(result.is_err());
+            assert_eq!(load_text(&path).unwrap(), "original");
+            assert_eq!(std::fs::read_dir(out_dir.path()).unwrap().count(), 1);
+
+            out_dir.close().unwrap();
+        }
     }
 }
@@ -4,8 +4,13 @@ use clap::Parser;
 
 mod ast;
 mod config;
+mod database;
 mod io;
 mod procedure;
+mod report;
+mod table;
+mod typescript;
+mod validation;
 
 fn main() {
     let cli = config::Cli::parse();
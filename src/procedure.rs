@@ -1,50 +1,451 @@
-use crate::config::Cli;
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap},
+};
+
+use chrono::{DateTime, Utc};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    ast::{directory_key, GhostData, GhostKey, Tombstone},
+    config::{Cli, Command, GlobalOptions, IdentityKey},
+};
 
 mod append;
 mod build;
+mod conflict;
 mod erase;
+mod export;
+mod import;
 mod merge;
+mod note;
+mod schema;
+mod tag;
+mod validate;
 
 pub(crate) fn procedure(config: &Cli) -> Result<(), std::io::Error> {
-    match config {
-        Cli::Append(args) => append::append(args),
-        Cli::Build(args) => build::build(args),
-        Cli::Erase(args) => erase::erase(args),
-        Cli::Merge(args) => merge::merge(args),
+    let options = config.global_options();
+
+    match config.command() {
+        Command::Append(args) => append::append(args, options),
+        Command::Build(args) => build::build(args, options),
+        Command::Erase(args) => erase::erase(args, options),
+        Command::Export(args) => export::export(args),
+        Command::Import(args) => import::import(args, options),
+        Command::Merge(args) => merge::merge(args, options),
+        Command::Note(args) => note::note(args),
+        Command::Schema(args) => schema::schema(args),
+        Command::Tag(args) => tag::tag(args),
+        Command::Validate(args) => validate::validate(args),
     }
 }
 
-fn unique_fold<T>(mut acc: Vec<T>, current: &T) -> Vec<T>
+/// directory, sakuraName and keroName, each of which is `None` when not a part of the key.
+type Key<'a> = (
+    Option<Cow<'a, str>>,
+    Option<Cow<'a, str>>,
+    Option<Option<Cow<'a, str>>>,
+);
+
+/// fields of `v` selected by `options`. equal keys mean the same ghost.
+fn key_of<'a>(v: &'a GhostData, options: &GlobalOptions) -> Key<'a> {
+    let field = |text: &'a str| {
+        if options.normalize() {
+            Cow::Owned(normalized(text))
+        } else {
+            Cow::Borrowed(text)
+        }
+    };
+
+    let (directory, sakura_name, kero_name) = v.key();
+    let directory = if options.normalize() {
+        Cow::Owned(normalized(&directory))
+    } else {
+        directory
+    };
+    match options.key() {
+        IdentityKey::All => (
+            Some(directory),
+            Some(field(sakura_name)),
+            Some(kero_name.map(field)),
+        ),
+        IdentityKey::Directory => (Some(directory), None, None),
+        IdentityKey::Names => (None, Some(field(sakura_name)), Some(kero_name.map(field))),
+    }
+}
+
+/// `text` for comparison: NFKC, without zero-width characters and surrounding spaces,
+/// and latin letters in lowercase.
+fn normalized(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter(|c| !matches!(c, '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}'))
+        .nfkc()
+        .collect();
+
+    let mut folded = String::with_capacity(text.len());
+    for c in text.trim().chars() {
+        if matches!(c, 'A'..='Z' | '\u{C0}'..='\u{24F}' | '\u{1E00}'..='\u{1EFF}') {
+            folded.extend(c.to_lowercase());
+        } else {
+            folded.push(c);
+        }
+    }
+    folded
+}
+
+/// ghost data without duplicates, in first-seen order. duplicates are absorbed into the first one.
+fn unique<'a, I>(ghost_list: I, options: &GlobalOptions) -> Vec<GhostData>
 where
-    T: PartialEq + Clone,
+    I: IntoIterator<Item = &'a GhostData>,
 {
-    if !acc.iter().any(|v| v == current) {
-        acc.push(current.clone());
+    let mut indices: HashMap<_, usize> = HashMap::new();
+    let mut acc: Vec<GhostData> = Vec::new();
+    for v in ghost_list {
+        match indices.entry(key_of(v, options)) {
+            Entry::Occupied(e) => acc[*e.get()].absorb(v),
+            Entry::Vacant(e) => {
+                e.insert(acc.len());
+                acc.push(v.clone());
+            }
+        }
     }
     acc
 }
 
+/// the latest tombstone for each ghost.
+fn latest_tombstones<'a, I>(tombstones: I) -> Vec<Tombstone>
+where
+    I: Iterator<Item = &'a Tombstone>,
+{
+    let mut indices: HashMap<_, usize> = HashMap::new();
+    let mut acc: Vec<Tombstone> = Vec::new();
+    for t in tombstones {
+        match indices.entry(t.ghost().key()) {
+            Entry::Occupied(e) => {
+                let v = &mut acc[*e.get()];
+                if t.erased_at() > v.erased_at() {
+                    *v = t.clone();
+                }
+            }
+            Entry::Vacant(e) => {
+                e.insert(acc.len());
+                acc.push(t.clone());
+            }
+        }
+    }
+    acc
+}
+
+/// times at which ghosts were erased by `tombstones`, by their keys.
+fn erased_times(tombstones: &[Tombstone]) -> HashMap<GhostKey<'_>, Option<DateTime<Utc>>> {
+    tombstones
+        .iter()
+        .map(|t| (t.ghost().key(), t.erased_at()))
+        .collect()
+}
+
+/// whether `v` from a list updated at `update` is buried by tombstones erased at `erased`.
+/// an entry comes back only when the list was updated after erasing it.
+fn is_buried(
+    erased: &HashMap<GhostKey<'_>, Option<DateTime<Utc>>>,
+    v: &GhostData,
+    update: Option<DateTime<Utc>>,
+) -> bool {
+    match erased.get(&v.key()) {
+        Some(erased_at) => match (update, erased_at) {
+            (Some(u), Some(e)) => u <= *e,
+            _ => true,
+        },
+        None => false,
+    }
+}
+
+/// copies tags and notes of `previous` to the same ghosts in `ghost_list`.
+fn carry_annotations(
+    ghost_list: &mut [GhostData],
+    previous: &[GhostData],
+    options: &GlobalOptions,
+) {
+    let mut previous_of = HashMap::new();
+    for p in previous.iter() {
+        previous_of.entry(key_of(p, options)).or_insert(p);
+    }
+
+    for v in ghost_list.iter_mut() {
+        if let Some(p) = previous_of.get(&key_of(v, options)) {
+            v.absorb_annotations(p);
+        }
+    }
+}
+
+/// whether `v` has any of specified values, requiring at least one of them.
+fn is_selected(
+    v: &GhostData,
+    directory: Option<&String>,
+    sakura_name: Option<&String>,
+    kero_name: Option<&String>,
+) -> Result<bool, std::io::Error> {
+    if directory.is_none() && sakura_name.is_none() && kero_name.is_none() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "specify at least one of directory, sakura-name or kero-name",
+        ));
+    }
+
+    Ok(has_any_of(v, directory, sakura_name, kero_name))
+}
+
+/// whether `v` has any of specified values. `erase`, `tag` and `note` select entries by this.
+fn has_any_of(
+    v: &GhostData,
+    directory: Option<&String>,
+    sakura_name: Option<&String>,
+    kero_name: Option<&String>,
+) -> bool {
+    directory.is_some_and(|d| directory_key(d) == directory_key(v.directory()))
+        || sakura_name.is_some_and(|s| s == v.sakura_name())
+        || kero_name.is_some_and(|k| Some(k) == v.kero_name())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    mod unique_fold {
+    mod unique {
         use super::*;
 
+        fn ghost(name: &str) -> GhostData {
+            GhostData::new(name.to_string(), name.to_string(), Some(name.to_string()))
+        }
+
         #[test]
         fn pushing_when_unique() {
-            let acc = vec!["a", "b"];
-            let current = "c";
-            let result = unique_fold(acc, &current);
-            assert_eq!(result, vec!["a", "b", "c"]);
+            let ghost_list = [ghost("a"), ghost("b"), ghost("c")];
+            let result = unique(&ghost_list, &GlobalOptions::default());
+            assert_eq!(result, vec![ghost("a"), ghost("b"), ghost("c")]);
         }
 
         #[test]
         fn not_pushing_when_not_unique() {
-            let acc = vec!["a", "b"];
-            let current = "b";
-            let result = unique_fold(acc, &current);
-            assert_eq!(result, vec!["a", "b"]);
+            let ghost_list = [ghost("b"), ghost("a"), ghost("b")];
+            let result = unique(&ghost_list, &GlobalOptions::default());
+            assert_eq!(result, vec![ghost("b"), ghost("a")]);
+        }
+
+        #[test]
+        fn not_same_when_no_kero_and_blank_kero() {
+            let no_kero = GhostData::new("a".to_string(), "a".to_string(), None);
+            let blank_kero = GhostData::new("a".to_string(), "a".to_string(), Some(String::new()));
+            let ghost_list = [no_kero.clone(), blank_kero.clone(), no_kero.clone()];
+            let result = unique(&ghost_list, &GlobalOptions::default());
+            assert_eq!(result, vec![no_kero, blank_kero]);
+        }
+
+        #[test]
+        fn keeping_unknown_fields_when_not_unique() {
+            let mut extra = serde_json::Map::new();
+            extra.insert("author".to_string(), serde_json::Value::Null);
+
+            let ghost_list = [ghost("a"), ghost("b"), ghost("b").with_extra(extra.clone())];
+            let result = unique(&ghost_list, &GlobalOptions::default());
+            assert_eq!(result, vec![ghost("a"), ghost("b").with_extra(extra)]);
+        }
+
+        #[test]
+        fn keeping_original_spelling_when_normalized() {
+            let ghost_list = [
+                ghost("ｻｸﾗ"),
+                ghost("サクラ"),
+                ghost("Alice"),
+                ghost("ａｌｉｃｅ　"),
+            ];
+            let result = unique(&ghost_list, &GlobalOptions::new(IdentityKey::All, true));
+            assert_eq!(result, vec![ghost("ｻｸﾗ"), ghost("Alice")]);
+        }
+
+        #[test]
+        fn same_when_directories_differ_only_in_case() {
+            let a = GhostData::new("Emily".to_string(), "s".to_string(), None);
+            let b = GhostData::new("emily ".to_string(), "s".to_string(), None);
+            let result = unique(&[a.clone(), b], &GlobalOptions::default());
+            assert_eq!(result, vec![a]);
+        }
+
+        #[test]
+        fn keeping_first_of_each_in_large_list() {
+            let ghost_list: Vec<GhostData> = (0..100_000)
+                .map(|i| ghost(&(i % 50_000).to_string()))
+                .collect();
+            let result = unique(&ghost_list, &GlobalOptions::default());
+            assert_eq!(result.len(), 50_000);
+            assert_eq!(result[0], ghost("0"));
+        }
+    }
+
+    mod key_of {
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let a = GhostData::new("d".to_string(), "s".to_string(), None);
+            let b = GhostData::new("d".to_string(), "s".to_string(), Some(String::new()));
+            let c = GhostData::new("other".to_string(), "s".to_string(), None);
+
+            assert_ne!(
+                key_of(&a, &GlobalOptions::default()),
+                key_of(&b, &GlobalOptions::default())
+            );
+            assert_eq!(
+                key_of(&a, &GlobalOptions::new(IdentityKey::Directory, false)),
+                key_of(&b, &GlobalOptions::new(IdentityKey::Directory, false))
+            );
+            assert_ne!(
+                key_of(&a, &GlobalOptions::new(IdentityKey::Directory, false)),
+                key_of(&c, &GlobalOptions::new(IdentityKey::Directory, false))
+            );
+            assert_eq!(
+                key_of(&a, &GlobalOptions::new(IdentityKey::Names, false)),
+                key_of(&c, &GlobalOptions::new(IdentityKey::Names, false))
+            );
+            assert_ne!(
+                key_of(&a, &GlobalOptions::new(IdentityKey::Names, false)),
+                key_of(&b, &GlobalOptions::new(IdentityKey::Names, false))
+            );
+        }
+
+        #[test]
+        fn same_when_normalized() {
+            let a = GhostData::new("ｻｸﾗ".to_string(), "Ａｌｉｃｅ　".to_string(), None);
+            let b = GhostData::new("サクラ".to_string(), "alice".to_string(), None);
+            let c = GhostData::new("サ\u{200B}クラ".to_string(), " ALICE".to_string(), None);
+            let raw = GlobalOptions::new(IdentityKey::All, false);
+            let normalized = GlobalOptions::new(IdentityKey::All, true);
+
+            assert_ne!(key_of(&a, &raw), key_of(&b, &raw));
+            assert_eq!(key_of(&a, &normalized), key_of(&b, &normalized));
+            assert_eq!(key_of(&a, &normalized), key_of(&c, &normalized));
+        }
+    }
+
+    mod normalized {
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            assert_eq!(normalized("ｻｸﾗ"), "サクラ");
+            assert_eq!(normalized("Ａｌｉｃｅ"), "alice");
+            assert_eq!(normalized("\u{3000}さくら\u{3000}"), "さくら");
+            assert_eq!(normalized("\u{FEFF}É\u{200D}"), "é");
+            assert_eq!(normalized("Ωメガ"), "Ωメガ");
+        }
+    }
+
+    mod latest_tombstones {
+        use chrono::{Duration, Utc};
+
+        use super::*;
+
+        #[test]
+        fn keeping_latest_one_for_each_ghost() {
+            let a = GhostData::new("a".to_string(), "a".to_string(), None);
+            let b = GhostData::new("b".to_string(), "b".to_string(), None);
+            let now = Utc::now();
+            let old_a = Tombstone::new(&a, now - Duration::days(1), Some("old".to_string()));
+            let new_a = Tombstone::new(&a, now, Some("new".to_string()));
+            let old_b = Tombstone::new(&b, now - Duration::days(1), None);
+
+            let tombstones = [old_a, old_b.clone(), new_a.clone()];
+            let result = latest_tombstones(tombstones.iter());
+            assert_eq!(result, vec![new_a, old_b]);
+        }
+    }
+
+    mod is_buried {
+        use chrono::Duration;
+
+        use super::*;
+
+        #[test]
+        fn true_when_same_ghost_in_older_list() {
+            let erased = GhostData::new("Emily".to_string(), "s".to_string(), None);
+            let tombstones = [Tombstone::new(&erased, Utc::now(), None)];
+            let result = erased_times(&tombstones);
+
+            let same = GhostData::new("emily.".to_string(), "s".to_string(), None);
+            assert!(is_buried(&result, &same, tombstones[0].erased_at()));
+            assert!(is_buried(&result, &same, None));
+        }
+
+        #[test]
+        fn false_when_list_is_newer_than_tombstone() {
+            let erased = GhostData::new("a".to_string(), "s".to_string(), None);
+            let erased_at = Utc::now() - Duration::days(1);
+            let tombstones = [Tombstone::new(&erased, erased_at, None)];
+            let result = erased_times(&tombstones);
+
+            assert!(!is_buried(&result, &erased, Some(Utc::now())));
+        }
+
+        #[test]
+        fn false_when_other_ghost() {
+            let erased = GhostData::new("emily".to_string(), "s".to_string(), None);
+            let tombstones = [Tombstone::new(&erased, Utc::now(), None)];
+            let result = erased_times(&tombstones);
+
+            let other = GhostData::new("emily".to_string(), "s".to_string(), Some(String::new()));
+            assert!(!is_buried(&result, &other, None));
+        }
+    }
+
+    mod carry_annotations {
+        use super::*;
+
+        #[test]
+        fn copying_to_same_ghost() {
+            let mut previous =
+                GhostData::new("a".to_string(), "a".to_string(), Some("a".to_string()));
+            previous.add_tag("retired");
+            previous.set_note(Some("known collision".to_string()));
+            let other = GhostData::new("b".to_string(), "b".to_string(), None);
+
+            let mut ghost_list = vec![
+                GhostData::new("a".to_string(), "a".to_string(), Some("a".to_string())),
+                GhostData::new("b".to_string(), "b".to_string(), Some("b".to_string())),
+            ];
+            carry_annotations(
+                &mut ghost_list,
+                &[previous.clone(), other],
+                &GlobalOptions::default(),
+            );
+            assert_eq!(ghost_list[0], previous);
+            assert!(ghost_list[1].tags().is_empty());
+            assert_eq!(ghost_list[1].note(), None);
+        }
+    }
+
+    mod is_selected {
+        use super::*;
+
+        #[test]
+        fn true_when_any_specified_value_matches() {
+            let v = GhostData::new("d".to_string(), "s".to_string(), Some("k".to_string()));
+            let d = "d".to_string();
+            let s = "s".to_string();
+            let k = "other".to_string();
+            assert!(is_selected(&v, Some(&d), None, None).unwrap());
+            assert!(is_selected(&v, Some(&d), Some(&s), None).unwrap());
+            assert!(is_selected(&v, Some(&d), Some(&s), Some(&k)).unwrap());
+            assert!(!is_selected(&v, None, None, Some(&k)).unwrap());
+
+            let other = GhostData::new("other".to_string(), "s".to_string(), None);
+            assert!(is_selected(&other, Some(&d), Some(&s), None).unwrap());
+        }
+
+        #[test]
+        fn failed_when_nothing_specified() {
+            let v = GhostData::new("d".to_string(), "s".to_string(), Some("k".to_string()));
+            assert!(is_selected(&v, None, None, None).is_err());
         }
     }
 }
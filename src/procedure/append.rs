@@ -1,15 +1,29 @@
+use std::collections::{HashMap, HashSet};
+
 use chrono::Utc;
 
 use crate::{
-    ast::{GhostData, GhostJson},
-    config::AppendArgs,
-    io::{load_json, write_json},
+    ast::{directory_key, GhostData, GhostJson},
+    config::{AppendArgs, ConflictPolicy, GlobalOptions},
+    io::{load_json, salvage_json, write_json, write_text},
 };
 
-use super::{build::read_ghost_collection, unique_fold};
+use super::{
+    build::read_ghost_collection,
+    conflict::{find_conflicts, Conflict, Resolutions},
+    erased_times, is_buried, unique,
+};
 
-pub(super) fn append(args: &AppendArgs) -> Result<(), std::io::Error> {
-    let json = load_json(args.output())?;
+pub(super) fn append(args: &AppendArgs, options: &GlobalOptions) -> Result<(), std::io::Error> {
+    let json = if args.lenient() {
+        let (json, problems) = salvage_json(args.output())?;
+        for problem in problems.iter() {
+            eprintln!("{}:{}", args.output().display(), problem);
+        }
+        json
+    } else {
+        load_json(args.output())?
+    };
 
     let mut ghost_list = Vec::new();
     for p in args.input().iter() {
@@ -18,32 +32,143 @@ pub(super) fn append(args: &AppendArgs) -> Result<(), std::io::Error> {
         }
     }
 
-    let json = append_body(&json, &ghost_list);
+    let json = if args.upsert() {
+        upsert_body(&json, &ghost_list)
+    } else {
+        let (json, conflicts) = append_body(&json, &ghost_list, args.policy(), options);
+        if let Some(path) = args.conflict_report() {
+            let report = serde_json::json!({ "conflicts": conflicts });
+            write_text(path, &serde_json::to_string_pretty(&report)?)?;
+        }
+        json
+    };
 
-    write_json(args.output(), &json)
+    write_json(args.output(), &json, args.write_options())
 }
 
-fn append_body(json: &GhostJson, appends: &[GhostData]) -> GhostJson {
-    println!(
+/// ghost data of `json` with `appends`, and conflicts between them.
+fn append_body(
+    json: &GhostJson,
+    appends: &[GhostData],
+    policy: ConflictPolicy,
+    options: &GlobalOptions,
+) -> (GhostJson, Vec<Conflict>) {
+    eprintln!(
         "raw length: original: {} + append: {} = {}",
         json.ghost_list().len(),
         appends.len(),
         json.ghost_list().len() + appends.len()
     );
 
-    let ghost_list = json
-        .ghost_list()
+    // freshly scanned data has no update time, so it never outlives a tombstone.
+    let erased = erased_times(json.tombstones());
+    let appends: Vec<GhostData> = appends
         .iter()
-        .chain(appends.iter())
-        .fold(Vec::new(), unique_fold);
+        .filter(|v| !is_buried(&erased, v, None))
+        .cloned()
+        .collect();
 
-    println!("result length: {}", ghost_list.len());
+    let sources = ["json".to_string(), "scanned".to_string()];
+    let jsons = [json.clone(), GhostJson::new(Utc::now(), appends)];
+    let mut conflicts = find_conflicts(&sources, &jsons, json.tombstones(), options);
+    for c in conflicts.iter_mut() {
+        c.resolve(policy);
+        eprintln!("{}", c);
+    }
+
+    let resolutions = Resolutions::new(&conflicts, options);
+    let jsons = resolutions.apply(&jsons);
+    let ghost_list = unique(jsons.iter().flat_map(|v| v.ghost_list().iter()), options);
 
-    GhostJson::new(Utc::now(), ghost_list)
+    eprintln!("result length: {}", ghost_list.len());
+
+    (json.updated(ghost_list), conflicts)
+}
+
+/// replaces entries of `json` with scanned ones of the same directory, and appends the others.
+/// tags, notes and unknown fields of replaced entries are kept.
+fn upsert_body(json: &GhostJson, appends: &[GhostData]) -> GhostJson {
+    let mut ghost_list = json.ghost_list().clone();
+    let mut indices: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, v) in ghost_list.iter().enumerate() {
+        indices
+            .entry(directory_key(v.directory()).into_owned())
+            .or_default()
+            .push(i);
+    }
+
+    let erased = erased_times(json.tombstones());
+    let mut removed = HashSet::new();
+    let (mut added, mut updated, mut unchanged) = (0, 0, 0);
+    for v in appends.iter() {
+        // freshly scanned data has no update time, so it never outlives a tombstone.
+        if is_buried(&erased, v, None) {
+            continue;
+        }
+
+        let same = indices
+            .entry(directory_key(v.directory()).into_owned())
+            .or_default();
+        match same[..] {
+            [] => {
+                eprintln!("added: `{}`: {}", v.directory(), names_of(v));
+                same.push(ghost_list.len());
+                ghost_list.push(v.clone());
+                added += 1;
+            }
+            [i] if ghost_list[i].is_same_ghost(v) => {
+                eprintln!("unchanged: `{}`: {}", v.directory(), names_of(v));
+                unchanged += 1;
+            }
+            _ => {
+                let before: Vec<String> = same.iter().map(|i| names_of(&ghost_list[*i])).collect();
+                eprintln!(
+                    "updated: `{}`: {} -> {}",
+                    v.directory(),
+                    before.join(", "),
+                    names_of(v)
+                );
+
+                let mut replaced = v.clone();
+                for i in same.iter() {
+                    replaced.absorb(&ghost_list[*i]);
+                }
+                ghost_list[same[0]] = replaced;
+                removed.extend(same.drain(1..));
+                updated += 1;
+            }
+        }
+    }
+
+    let ghost_list: Vec<GhostData> = ghost_list
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !removed.contains(i))
+        .map(|(_, v)| v)
+        .collect();
+
+    eprintln!(
+        "added: {}, updated: {}, unchanged: {}, result length: {}",
+        added,
+        updated,
+        unchanged,
+        ghost_list.len()
+    );
+
+    json.updated(ghost_list)
+}
+
+fn names_of(v: &GhostData) -> String {
+    match v.kero_name() {
+        Some(k) => format!("`{}` / `{}`", v.sakura_name(), k),
+        None => format!("`{}` / no kero", v.sakura_name()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::config::WriteOptions;
+
     use super::*;
 
     mod append {
@@ -65,44 +190,138 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
-            write_json(&json_path, &json).unwrap();
+            write_json(&json_path, &json, &WriteOptions::default()).unwrap();
 
             let input = vec![
                 PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/ghost/valid"),
                 PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/ghost/invalid"),
             ];
 
-            let args = AppendArgs::new(input.clone(), json_path.clone());
+            let args = AppendArgs::new(
+                input.clone(),
+                json_path.clone(),
+                ConflictPolicy::KeepAll,
+                false,
+                false,
+            );
 
-            append(&args).unwrap();
+            append(&args, &GlobalOptions::default()).unwrap();
 
             let result = load_json(&json_path).unwrap();
             assert_eq!(
                 result.ghost_list(),
                 &vec![
-                    GhostData::new("a_d".to_string(), "a_s".to_string(), "a_k".to_string(),),
+                    GhostData::new(
+                        "a_d".to_string(),
+                        "a_s".to_string(),
+                        Some("a_k".to_string()),
+                    ),
                     GhostData::new(
                         "aaa".to_string(),
                         "さくらAAA".to_string(),
-                        "ケロAAA".to_string()
+                        Some("ケロAAA".to_string())
                     ),
-                    GhostData::new("bbb".to_string(), "さくらBBB".to_string(), "".to_string()),
+                    GhostData::new("bbb".to_string(), "さくらBBB".to_string(), None),
                     GhostData::new(
                         "ccc".to_string(),
                         "さくらCCC".to_string(),
-                        "ケロCCC".to_string()
+                        Some("ケロCCC".to_string())
                     )
                 ]
             );
             out_dir.close().unwrap();
         }
+
+        #[test]
+        fn writing_conflict_report() {
+            let out_dir = tempdir().unwrap();
+
+            let json_path = out_dir.path().join("json.json");
+            let json = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new(
+                    "aaa".to_string(),
+                    "さくらOLD".to_string(),
+                    Some("ケロAAA".to_string()),
+                )],
+            );
+            write_json(&json_path, &json, &WriteOptions::default()).unwrap();
+
+            let report_path = out_dir.path().join("conflicts.json");
+            let input =
+                vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/ghost/valid")];
+            let args = AppendArgs::new(
+                input,
+                json_path.clone(),
+                ConflictPolicy::Scanned,
+                false,
+                false,
+            )
+            .with_conflict_report(Some(report_path.clone()));
+
+            append(&args, &GlobalOptions::default()).unwrap();
+
+            let report: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+            let conflicts = report["conflicts"].as_array().unwrap();
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0]["directory"], "aaa");
+            assert_eq!(conflicts[0]["resolution"], "scanned");
+            let versions = &conflicts[0]["versions"];
+            assert_eq!(versions[0]["sakuraName"], "さくらOLD");
+            assert_eq!(versions[0]["sources"][0], "json");
+            assert_eq!(versions[0]["kept"], false);
+            assert_eq!(versions[1]["sakuraName"], "さくらAAA");
+            assert_eq!(versions[1]["sources"][0], "scanned");
+            assert_eq!(versions[1]["kept"], true);
+
+            let result = load_json(&json_path).unwrap();
+            assert_eq!(result.ghost_list()[0].sakura_name(), "さくらAAA");
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn matching_no_kero_in_older_list() {
+            let out_dir = tempdir().unwrap();
+
+            let json_path = out_dir.path().join("json.json");
+            std::fs::write(
+                &json_path,
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"bbb","sakuraName":"さくらBBB","keroName":"","tags":["retired"]}]}"#,
+            )
+            .unwrap();
+
+            let input =
+                vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/ghost/valid")];
+            let args = AppendArgs::new(
+                input,
+                json_path.clone(),
+                ConflictPolicy::KeepAll,
+                false,
+                false,
+            );
+
+            append(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&json_path).unwrap();
+            let bbb: Vec<_> = result
+                .ghost_list()
+                .iter()
+                .filter(|v| v.directory() == "bbb")
+                .collect();
+            assert_eq!(bbb.len(), 1);
+            assert_eq!(bbb[0].kero_name(), None);
+            assert_eq!(bbb[0].tags(), &vec!["retired".to_string()]);
+            out_dir.close().unwrap();
+        }
     }
 
     mod append_body {
-        use crate::ast::GhostData;
+        use crate::ast::{GhostData, Tombstone};
 
         use super::*;
 
@@ -113,21 +332,46 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
             let appends = vec![
-                GhostData::new("b_d".to_string(), "b_s".to_string(), "b_k".to_string()),
-                GhostData::new("c_d".to_string(), "c_s".to_string(), "c_k".to_string()),
+                GhostData::new(
+                    "b_d".to_string(),
+                    "b_s".to_string(),
+                    Some("b_k".to_string()),
+                ),
+                GhostData::new(
+                    "c_d".to_string(),
+                    "c_s".to_string(),
+                    Some("c_k".to_string()),
+                ),
             ];
 
-            let result = append_body(&json, &appends);
+            let (result, _) = append_body(
+                &json,
+                &appends,
+                ConflictPolicy::KeepAll,
+                &GlobalOptions::default(),
+            );
             assert_eq!(
                 result.ghost_list(),
                 &vec![
-                    GhostData::new("a_d".to_string(), "a_s".to_string(), "a_k".to_string()),
-                    GhostData::new("b_d".to_string(), "b_s".to_string(), "b_k".to_string()),
-                    GhostData::new("c_d".to_string(), "c_s".to_string(), "c_k".to_string())
+                    GhostData::new(
+                        "a_d".to_string(),
+                        "a_s".to_string(),
+                        Some("a_k".to_string())
+                    ),
+                    GhostData::new(
+                        "b_d".to_string(),
+                        "b_s".to_string(),
+                        Some("b_k".to_string())
+                    ),
+                    GhostData::new(
+                        "c_d".to_string(),
+                        "c_s".to_string(),
+                        Some("c_k".to_string())
+                    )
                 ]
             );
         }
@@ -139,23 +383,182 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
             let appends = vec![
-                GhostData::new("a_d".to_string(), "a_s".to_string(), "a_k".to_string()),
-                GhostData::new("a_d".to_string(), "a_s".to_string(), "a_k".to_string()),
+                GhostData::new(
+                    "a_d".to_string(),
+                    "a_s".to_string(),
+                    Some("a_k".to_string()),
+                ),
+                GhostData::new(
+                    "a_d".to_string(),
+                    "a_s".to_string(),
+                    Some("a_k".to_string()),
+                ),
             ];
 
-            let result = append_body(&json, &appends);
+            let (result, _) = append_body(
+                &json,
+                &appends,
+                ConflictPolicy::KeepAll,
+                &GlobalOptions::default(),
+            );
             assert_eq!(
                 result.ghost_list(),
                 &vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string()
+                    Some("a_k".to_string())
                 ),]
             );
         }
+
+        #[test]
+        fn keeping_tags_and_note_when_not_unique() {
+            let mut original = GhostData::new(
+                "a_d".to_string(),
+                "a_s".to_string(),
+                Some("a_k".to_string()),
+            );
+            original.add_tag("retired");
+            original.set_note(Some("note".to_string()));
+            let json = GhostJson::new(Utc::now(), vec![original.clone()]);
+            let appends = vec![GhostData::new(
+                "a_d".to_string(),
+                "a_s".to_string(),
+                Some("a_k".to_string()),
+            )];
+
+            let (result, _) = append_body(
+                &json,
+                &appends,
+                ConflictPolicy::KeepAll,
+                &GlobalOptions::default(),
+            );
+            assert_eq!(result.ghost_list(), &vec![original]);
+        }
+
+        #[test]
+        fn not_appending_when_buried() {
+            let buried = GhostData::new("b_d".to_string(), "b_s".to_string(), None);
+            let json = GhostJson::new(Utc::now(), vec![]).with_tombstones(vec![Tombstone::new(
+                &buried,
+                Utc::now(),
+                None,
+            )]);
+            let appends = vec![
+                buried,
+                GhostData::new("c_d".to_string(), "c_s".to_string(), None),
+            ];
+
+            let (result, _) = append_body(
+                &json,
+                &appends,
+                ConflictPolicy::KeepAll,
+                &GlobalOptions::default(),
+            );
+            assert_eq!(
+                result.ghost_list(),
+                &vec![GhostData::new("c_d".to_string(), "c_s".to_string(), None)]
+            );
+            assert_eq!(result.tombstones(), json.tombstones());
+        }
+
+        #[test]
+        fn resolving_conflicts_by_policy() {
+            let json = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("a_d".to_string(), "a_s".to_string(), None)],
+            );
+            let appends = vec![GhostData::new(
+                "a_d".to_string(),
+                "renamed".to_string(),
+                None,
+            )];
+
+            let (result, _) = append_body(
+                &json,
+                &appends,
+                ConflictPolicy::KeepAll,
+                &GlobalOptions::default(),
+            );
+            assert_eq!(result.ghost_list().len(), 2);
+
+            let (result, _) = append_body(
+                &json,
+                &appends,
+                ConflictPolicy::Priority,
+                &GlobalOptions::default(),
+            );
+            assert_eq!(result.ghost_list().len(), 1);
+            assert_eq!(result.ghost_list()[0].sakura_name(), "a_s");
+            assert!(result.ghost_list()[0].extra().is_empty());
+
+            let (result, _) = append_body(
+                &json,
+                &appends,
+                ConflictPolicy::Scanned,
+                &GlobalOptions::default(),
+            );
+            assert_eq!(result.ghost_list().len(), 1);
+            assert_eq!(result.ghost_list()[0].sakura_name(), "renamed");
+            assert!(result.ghost_list()[0].extra().is_empty());
+        }
+    }
+
+    mod upsert_body {
+        use crate::ast::Tombstone;
+
+        use super::*;
+
+        #[test]
+        fn replacing_entries_of_same_directory() {
+            let mut renamed = GhostData::new(
+                "a_d".to_string(),
+                "a_s".to_string(),
+                Some("a_k".to_string()),
+            );
+            renamed.add_tag("retired");
+            let buried = GhostData::new("d_d".to_string(), "d_s".to_string(), None);
+            let json = GhostJson::new(
+                Utc::now(),
+                vec![
+                    renamed,
+                    GhostData::new("b_d".to_string(), "b_s".to_string(), None),
+                    GhostData::new("A_d.".to_string(), "old".to_string(), None),
+                ],
+            )
+            .with_tombstones(vec![Tombstone::new(&buried, Utc::now(), None)]);
+            let appends = vec![
+                GhostData::new(
+                    "a_d".to_string(),
+                    "a_s".to_string(),
+                    Some("new_k".to_string()),
+                ),
+                GhostData::new("b_d".to_string(), "b_s".to_string(), None),
+                GhostData::new("c_d".to_string(), "c_s".to_string(), None),
+                buried,
+            ];
+
+            let result = upsert_body(&json, &appends);
+
+            let mut expected = GhostData::new(
+                "a_d".to_string(),
+                "a_s".to_string(),
+                Some("new_k".to_string()),
+            );
+            expected.add_tag("retired");
+            assert_eq!(
+                result.ghost_list(),
+                &vec![
+                    expected,
+                    GhostData::new("b_d".to_string(), "b_s".to_string(), None),
+                    GhostData::new("c_d".to_string(), "c_s".to_string(), None),
+                ]
+            );
+            assert_eq!(result.tombstones(), json.tombstones());
+        }
     }
 }
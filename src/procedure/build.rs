@@ -4,12 +4,12 @@ use chrono::Utc;
 
 use crate::{
     ast::{GhostData, GhostJson},
-    config::BuildArgs,
-    io::{load_setting_file, write_json},
-    procedure::unique_fold,
+    config::{BuildArgs, GlobalOptions},
+    io::{is_std_stream, load_json, load_setting_file, write_json},
+    procedure::{carry_annotations, erased_times, is_buried, unique},
 };
 
-pub(super) fn build(args: &BuildArgs) -> Result<(), std::io::Error> {
+pub(super) fn build(args: &BuildArgs, options: &GlobalOptions) -> Result<(), std::io::Error> {
     let mut ghost_list = Vec::new();
 
     for p in args.input().iter() {
@@ -18,15 +18,36 @@ pub(super) fn build(args: &BuildArgs) -> Result<(), std::io::Error> {
         }
     }
 
-    println!("raw length: {}", ghost_list.len());
+    eprintln!("raw length: {}", ghost_list.len());
 
-    let ghost_list = ghost_list.iter().fold(Vec::new(), unique_fold);
+    let mut previous = None;
+    if !is_std_stream(args.output()) && args.output().is_file() {
+        match load_json(args.output()) {
+            Ok(v) => previous = Some(v),
+            Err(e) => eprintln!("{}: {}", args.output().display(), e),
+        }
+    }
+
+    // tombstones and unknown fields of the previous output are kept.
+    // freshly scanned data has no update time, so it never outlives a tombstone.
+    if let Some(previous) = previous.as_ref() {
+        let erased = erased_times(previous.tombstones());
+        ghost_list.retain(|v| !is_buried(&erased, v, None));
+    }
 
-    println!("result length: {}", ghost_list.len());
+    let mut ghost_list = unique(&ghost_list, options);
 
-    let json = GhostJson::new(Utc::now(), ghost_list);
+    eprintln!("result length: {}", ghost_list.len());
 
-    write_json(args.output(), &json)
+    let json = match previous {
+        Some(previous) => {
+            carry_annotations(&mut ghost_list, previous.ghost_list(), options);
+            previous.updated(ghost_list)
+        }
+        None => GhostJson::new(Utc::now(), ghost_list),
+    };
+
+    write_json(args.output(), &json, args.write_options())
 }
 
 pub(crate) fn read_ghost_collection<P>(dir_path: P) -> Option<Vec<GhostData>>
@@ -42,17 +63,20 @@ where
         }
     };
 
-    let mut ghost_list = Vec::new();
+    // read_dir yields entries in no particular order; sort them so the
+    // generated list does not depend on the filesystem.
+    let mut paths = Vec::new();
     for entry in entries {
-        let entry = match entry {
-            Ok(v) => v,
-            Err(e) => {
-                eprintln!("{}", e);
-                continue;
-            }
-        };
+        match entry {
+            Ok(v) => paths.push(v.path()),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    paths.sort();
 
-        match read_ghost_data(entry.path()) {
+    let mut ghost_list = Vec::new();
+    for path in paths {
+        match read_ghost_data(path) {
             Ok(v) => ghost_list.push(v),
             Err(e) => {
                 eprintln!("{}", e);
@@ -117,7 +141,7 @@ where
         .map(|v| Some(v.to_string()))
 }
 
-fn read_names_from_descript<P>(dir_path: P) -> Result<(String, String), std::io::Error>
+fn read_names_from_descript<P>(dir_path: P) -> Result<(String, Option<String>), std::io::Error>
 where
     P: AsRef<Path>,
 {
@@ -137,8 +161,7 @@ where
     let kero_name = contents
         .lines()
         .find_map(|v| get_labeled_value_from_line(v, "kero.name,"))
-        .unwrap_or("")
-        .to_string();
+        .map(|v| v.to_string());
 
     Ok((sakura_name, kero_name))
 }
@@ -150,6 +173,8 @@ fn get_labeled_value_from_line<'a>(line: &'a str, label: &str) -> Option<&'a str
 
 #[cfg(test)]
 mod tests {
+    use crate::config::WriteOptions;
+
     use super::*;
 
     mod build {
@@ -157,7 +182,10 @@ mod tests {
 
         use tempfile::tempdir;
 
-        use crate::io::load_json;
+        use crate::{
+            config::{ConflictPolicy, EraseArgs, MergeArgs},
+            procedure::{erase::erase, merge::merge},
+        };
 
         use super::*;
 
@@ -172,7 +200,7 @@ mod tests {
             ];
             let args = BuildArgs::new(input, out_path.clone());
 
-            build(&args).unwrap();
+            build(&args, &GlobalOptions::default()).unwrap();
 
             let result = load_json(&out_path).unwrap();
             assert_eq!(
@@ -181,19 +209,100 @@ mod tests {
                     GhostData::new(
                         "aaa".to_string(),
                         "さくらAAA".to_string(),
-                        "ケロAAA".to_string()
+                        Some("ケロAAA".to_string())
                     ),
-                    GhostData::new("bbb".to_string(), "さくらBBB".to_string(), "".to_string()),
+                    GhostData::new("bbb".to_string(), "さくらBBB".to_string(), None),
                     GhostData::new(
                         "ccc".to_string(),
                         "さくらCCC".to_string(),
-                        "ケロCCC".to_string()
+                        Some("ケロCCC".to_string())
                     )
                 ]
             );
 
             out_dir.close().unwrap();
         }
+
+        #[test]
+        fn carrying_tags_and_note_over() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.json");
+            let original_contents = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"aaa","sakuraName":"さくらAAA","keroName":"ケロAAA","tags":["retired"],"note":"note"},{"directory":"zzz","sakuraName":"さくらZZZ","keroName":"","tags":["test"]}]}"#;
+            let original_json = serde_json::from_str(original_contents).unwrap();
+            write_json(&out_path, &original_json, &WriteOptions::default()).unwrap();
+
+            let input =
+                vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/ghost/valid")];
+            let args = BuildArgs::new(input, out_path.clone());
+
+            build(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(result.ghost_list().len(), 3);
+            assert_eq!(result.ghost_list()[0].tags(), &vec!["retired".to_string()]);
+            assert_eq!(result.ghost_list()[0].note(), Some(&"note".to_string()));
+            assert!(result.ghost_list()[1].tags().is_empty());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn keeping_erased_ghost_out_through_merge() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.json");
+            let old_path = out_dir.path().join("old.json");
+            let merged_path = out_dir.path().join("merged.json");
+
+            let input =
+                vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/ghost/valid")];
+            let args = BuildArgs::new(input, out_path.clone());
+            build(&args, &GlobalOptions::default()).unwrap();
+
+            let mut extra = serde_json::Map::new();
+            extra.insert("source".to_string(), serde_json::Value::from("team"));
+            let built = load_json(&out_path).unwrap().with_extra(extra);
+            write_json(&out_path, &built, &WriteOptions::default()).unwrap();
+            write_json(&old_path, &built, &WriteOptions::default()).unwrap();
+
+            let erase_args = EraseArgs::new(
+                out_path.clone(),
+                Some("bbb".to_string()),
+                None,
+                None,
+                true,
+                None,
+            );
+            erase(&erase_args, &GlobalOptions::default()).unwrap();
+
+            build(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(result.ghost_list().len(), 2);
+            assert!(result.ghost_list().iter().all(|v| v.directory() != "bbb"));
+            assert_eq!(result.tombstones().len(), 1);
+            assert_eq!(
+                result.extra().get("source"),
+                Some(&serde_json::Value::from("team"))
+            );
+
+            let merge_args = MergeArgs::new(
+                vec![old_path, out_path],
+                false,
+                merged_path.clone(),
+                None,
+                None,
+                false,
+                ConflictPolicy::KeepAll,
+            );
+            merge(&merge_args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&merged_path).unwrap();
+            assert_eq!(result.ghost_list().len(), 2);
+            assert!(result.ghost_list().iter().all(|v| v.directory() != "bbb"));
+            assert_eq!(result.tombstones().len(), 1);
+
+            out_dir.close().unwrap();
+        }
     }
 
     mod read_ghost_collection {
@@ -212,18 +321,36 @@ mod tests {
                     GhostData::new(
                         "aaa".to_string(),
                         "さくらAAA".to_string(),
-                        "ケロAAA".to_string()
+                        Some("ケロAAA".to_string())
                     ),
-                    GhostData::new("bbb".to_string(), "さくらBBB".to_string(), "".to_string()),
+                    GhostData::new("bbb".to_string(), "さくらBBB".to_string(), None),
                     GhostData::new(
                         "ccc".to_string(),
                         "さくらCCC".to_string(),
-                        "ケロCCC".to_string()
+                        Some("ケロCCC".to_string())
                     )
                 ]
             );
         }
 
+        #[test]
+        fn sorting_by_directory_path() {
+            let dir = tempfile::tempdir().unwrap();
+            for name in ["ccc", "aaa", "bbb"] {
+                let master = dir.path().join(name).join("ghost/master");
+                std::fs::create_dir_all(&master).unwrap();
+                std::fs::write(
+                    master.join("descript.txt"),
+                    format!("charset,UTF-8\nsakura.name,{}\n", name),
+                )
+                .unwrap();
+            }
+
+            let result = read_ghost_collection(dir.path()).unwrap();
+            let directories: Vec<&str> = result.iter().map(|v| v.directory().as_str()).collect();
+            assert_eq!(directories, vec!["aaa", "bbb", "ccc"]);
+        }
+
         #[test]
         fn none_when_invalid_dir() {
             let dir_path =
@@ -313,7 +440,16 @@ mod tests {
                 PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/ghost/valid/aaa");
             let (sakura_name, kero_name) = read_names_from_descript(&dir_path).unwrap();
             assert_eq!(sakura_name, "さくらAAA".to_string());
-            assert_eq!(kero_name, "ケロAAA".to_string());
+            assert_eq!(kero_name, Some("ケロAAA".to_string()));
+        }
+
+        #[test]
+        fn success_none_kero_when_no_kero_name() {
+            let dir_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/ghost/valid/bbb");
+            let (sakura_name, kero_name) = read_names_from_descript(&dir_path).unwrap();
+            assert_eq!(sakura_name, "さくらBBB".to_string());
+            assert_eq!(kero_name, None);
         }
     }
 
@@ -0,0 +1,405 @@
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    ast::{directory_key, GhostData, GhostJson, Tombstone},
+    config::{ConflictPolicy, GlobalOptions},
+};
+
+use super::{erased_times, is_buried, key_of};
+
+/// versions of ghost data sharing a directory but disagreeing in names.
+/// directories are compared by `directory_key`, and `directory` is the first-seen spelling.
+/// versions are compared by `key_of`, so that they differ only when deduplication tells them apart.
+#[derive(Serialize, Debug, PartialEq)]
+pub(super) struct Conflict {
+    directory: String,
+    /// policy which decided the kept version. `None` while unresolved.
+    resolution: Option<String>,
+    versions: Vec<ConflictVersion>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct ConflictVersion {
+    #[serde(rename = "sakuraName")]
+    sakura_name: String,
+    /// `None` when the ghost has no kero.
+    #[serde(rename = "keroName")]
+    kero_name: Option<String>,
+    /// inputs which have this version.
+    sources: Vec<String>,
+    kept: bool,
+    /// the newest `update` of the inputs.
+    #[serde(skip)]
+    update: Option<DateTime<Utc>>,
+    /// index of the last input which has this version.
+    #[serde(skip)]
+    last_source: usize,
+    /// the first-seen entry of this version, to compare with others by `key_of`.
+    #[serde(skip)]
+    ghost: GhostData,
+}
+
+impl Conflict {
+    pub fn is_resolved(&self) -> bool {
+        self.resolution.is_some()
+    }
+
+    /// keeps only the version `policy` prefers. ties are broken by the earlier input.
+    pub fn resolve(&mut self, policy: ConflictPolicy) {
+        let kept = match policy {
+            ConflictPolicy::KeepAll => return,
+            ConflictPolicy::Priority => 0,
+            ConflictPolicy::Newest => index_of_max(self.versions.iter().map(|v| v.update)),
+            ConflictPolicy::Scanned => index_of_max(self.versions.iter().map(|v| v.last_source)),
+        };
+
+        for (i, v) in self.versions.iter_mut().enumerate() {
+            v.kept = i == kept;
+        }
+        self.resolution = policy.to_possible_value().map(|v| v.get_name().to_string());
+    }
+
+    fn version_of(&self, v: &GhostData, options: &GlobalOptions) -> Option<&ConflictVersion> {
+        let key = key_of(v, options);
+        self.versions
+            .iter()
+            .find(|c| key_of(&c.ghost, options) == key)
+    }
+}
+
+/// index of the first maximum.
+fn index_of_max<T, I>(values: I) -> usize
+where
+    T: PartialOrd,
+    I: Iterator<Item = T>,
+{
+    let mut result: Option<(usize, T)> = None;
+    for (i, v) in values.enumerate() {
+        match &result {
+            Some((_, max)) if v <= *max => {}
+            _ => result = Some((i, v)),
+        }
+    }
+    result.map(|(i, _)| i).unwrap_or(0)
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "conflict: directory `{}`", self.directory)?;
+        if let Some(resolution) = &self.resolution {
+            write!(f, " (resolved by {})", resolution)?;
+        }
+        for v in self.versions.iter() {
+            let kero_name = match &v.kero_name {
+                Some(k) => format!("`{}`", k),
+                None => "no kero".to_string(),
+            };
+            let mark = match (self.is_resolved(), v.kept) {
+                (false, _) => "",
+                (true, true) => "kept ",
+                (true, false) => "dropped ",
+            };
+            write!(
+                f,
+                "\n  {}`{}` / {}: {}",
+                mark,
+                v.sakura_name,
+                kero_name,
+                v.sources.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// conflicts among entries of `jsons` not buried by `tombstones`, in first-seen order.
+/// `sources` are names of `jsons` in the same order.
+pub(super) fn find_conflicts(
+    sources: &[String],
+    jsons: &[GhostJson],
+    tombstones: &[Tombstone],
+    options: &GlobalOptions,
+) -> Vec<Conflict> {
+    let erased = erased_times(tombstones);
+    let mut indices: HashMap<Cow<str>, usize> = HashMap::new();
+    let mut conflicts: Vec<Conflict> = Vec::new();
+
+    for (i, (source, json)) in sources.iter().zip(jsons.iter()).enumerate() {
+        let update = json.update_time();
+        for v in json.ghost_list().iter() {
+            if is_buried(&erased, v, update) {
+                continue;
+            }
+
+            let index = *indices
+                .entry(directory_key(v.directory()))
+                .or_insert_with(|| {
+                    conflicts.push(Conflict {
+                        directory: v.directory().clone(),
+                        resolution: None,
+                        versions: Vec::new(),
+                    });
+                    conflicts.len() - 1
+                });
+            let versions = &mut conflicts[index].versions;
+
+            let key = key_of(v, options);
+            let version = versions
+                .iter_mut()
+                .find(|c| key_of(&c.ghost, options) == key);
+            match version {
+                Some(c) => {
+                    if !c.sources.contains(source) {
+                        c.sources.push(source.clone());
+                    }
+                    c.update = c.update.max(update);
+                    c.last_source = i;
+                }
+                None => versions.push(ConflictVersion {
+                    sakura_name: v.sakura_name().clone(),
+                    kero_name: v.kero_name().cloned(),
+                    sources: vec![source.clone()],
+                    kept: true,
+                    update,
+                    last_source: i,
+                    ghost: v.clone(),
+                }),
+            }
+        }
+    }
+
+    conflicts.retain(|c| c.versions.len() > 1);
+    conflicts
+}
+
+/// resolved conflicts looked up by `directory_key`.
+pub(super) struct Resolutions<'a> {
+    conflicts: HashMap<Cow<'a, str>, &'a Conflict>,
+    options: &'a GlobalOptions,
+}
+
+impl<'a> Resolutions<'a> {
+    pub fn new(conflicts: &'a [Conflict], options: &'a GlobalOptions) -> Resolutions<'a> {
+        Resolutions {
+            conflicts: conflicts
+                .iter()
+                .filter(|c| c.is_resolved())
+                .map(|c| (directory_key(&c.directory), c))
+                .collect(),
+            options,
+        }
+    }
+
+    fn version_of(&self, v: &GhostData) -> Option<&'a ConflictVersion> {
+        self.conflicts
+            .get(&directory_key(v.directory()))?
+            .version_of(v, self.options)
+    }
+
+    /// whether `v` is a version dropped by resolution.
+    pub fn is_dropped(&self, v: &GhostData) -> bool {
+        self.version_of(v).is_some_and(|version| !version.kept)
+    }
+
+    /// whether `v` is the version kept by resolution.
+    pub fn is_kept(&self, v: &GhostData) -> bool {
+        self.version_of(v).is_some_and(|version| version.kept)
+    }
+
+    /// copies of `jsons` without dropped versions.
+    pub fn apply(&self, jsons: &[GhostJson]) -> Vec<GhostJson> {
+        jsons
+            .iter()
+            .map(|json| {
+                let ghost_list = json
+                    .ghost_list()
+                    .iter()
+                    .filter(|v| !self.is_dropped(v))
+                    .cloned()
+                    .collect();
+                json.clone().with_ghost_list(ghost_list)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn sample() -> (Vec<String>, Vec<GhostJson>) {
+        let sources = vec!["a.json".to_string(), "b.json".to_string()];
+        let json_a = GhostJson::new(
+            Utc::now() - Duration::days(2),
+            vec![
+                GhostData::new("a".to_string(), "s".to_string(), Some("k".to_string())),
+                GhostData::new("b".to_string(), "s".to_string(), None),
+            ],
+        );
+        let json_b = GhostJson::new(
+            Utc::now() - Duration::days(1),
+            vec![
+                GhostData::new("a".to_string(), "renamed".to_string(), None),
+                GhostData::new("b".to_string(), "s".to_string(), None),
+            ],
+        );
+        (sources, vec![json_a, json_b])
+    }
+
+    mod find_conflicts {
+        use super::*;
+
+        #[test]
+        fn grouping_versions_by_directory() {
+            let sources = vec!["a.json".to_string(), "b.json".to_string()];
+            let buried = GhostData::new("a".to_string(), "erased".to_string(), None);
+            let json_a = GhostJson::new(
+                Utc::now() - Duration::days(1),
+                vec![
+                    GhostData::new("a".to_string(), "s".to_string(), Some("k".to_string())),
+                    GhostData::new("b".to_string(), "s".to_string(), None),
+                    buried.clone(),
+                ],
+            );
+            let update = json_a.update_time();
+            let json_b = GhostJson::new(
+                update.unwrap(),
+                vec![
+                    GhostData::new("a".to_string(), "s".to_string(), Some("k".to_string())),
+                    GhostData::new("a".to_string(), "renamed".to_string(), None),
+                    GhostData::new("b".to_string(), "s".to_string(), None),
+                ],
+            );
+            let tombstones = vec![Tombstone::new(&buried, Utc::now(), None)];
+
+            let result = find_conflicts(
+                &sources,
+                &[json_a, json_b],
+                &tombstones,
+                &GlobalOptions::default(),
+            );
+            assert_eq!(
+                result,
+                vec![Conflict {
+                    directory: "a".to_string(),
+                    resolution: None,
+                    versions: vec![
+                        ConflictVersion {
+                            sakura_name: "s".to_string(),
+                            kero_name: Some("k".to_string()),
+                            sources: sources.clone(),
+                            kept: true,
+                            update,
+                            last_source: 1,
+                            ghost: GhostData::new(
+                                "a".to_string(),
+                                "s".to_string(),
+                                Some("k".to_string()),
+                            ),
+                        },
+                        ConflictVersion {
+                            sakura_name: "renamed".to_string(),
+                            kero_name: None,
+                            sources: vec!["b.json".to_string()],
+                            kept: true,
+                            update,
+                            last_source: 1,
+                            ghost: GhostData::new("a".to_string(), "renamed".to_string(), None),
+                        },
+                    ],
+                }]
+            );
+            assert_eq!(
+                result[0].to_string(),
+                "conflict: directory `a`\n  `s` / `k`: a.json, b.json\n  `renamed` / no kero: b.json"
+            );
+        }
+    }
+
+    mod resolve {
+        use super::*;
+
+        fn kept_names(conflict: &Conflict) -> Vec<&str> {
+            conflict
+                .versions
+                .iter()
+                .filter(|v| v.kept)
+                .map(|v| v.sakura_name.as_str())
+                .collect()
+        }
+
+        #[test]
+        fn checking_each_policy() {
+            let (sources, jsons) = sample();
+
+            let mut conflicts = find_conflicts(&sources, &jsons, &[], &GlobalOptions::default());
+            conflicts[0].resolve(ConflictPolicy::KeepAll);
+            assert!(!conflicts[0].is_resolved());
+            assert_eq!(kept_names(&conflicts[0]), vec!["s", "renamed"]);
+
+            conflicts[0].resolve(ConflictPolicy::Priority);
+            assert_eq!(conflicts[0].resolution, Some("priority".to_string()));
+            assert_eq!(kept_names(&conflicts[0]), vec!["s"]);
+
+            conflicts[0].resolve(ConflictPolicy::Newest);
+            assert_eq!(kept_names(&conflicts[0]), vec!["renamed"]);
+
+            conflicts[0].resolve(ConflictPolicy::Scanned);
+            assert_eq!(kept_names(&conflicts[0]), vec!["renamed"]);
+            assert!(conflicts[0].to_string().contains("\n  kept `renamed`"));
+        }
+    }
+
+    mod normalize {
+        use crate::config::IdentityKey;
+
+        use super::*;
+
+        #[test]
+        fn not_conflict_when_same_after_normalization() {
+            let sources = vec!["a.json".to_string(), "b.json".to_string()];
+            let jsons = [
+                GhostJson::new(
+                    Utc::now(),
+                    vec![GhostData::new("a".to_string(), "ｻｸﾗ".to_string(), None)],
+                ),
+                GhostJson::new(
+                    Utc::now(),
+                    vec![GhostData::new("a".to_string(), "サクラ".to_string(), None)],
+                ),
+            ];
+
+            let result = find_conflicts(&sources, &jsons, &[], &GlobalOptions::default());
+            assert_eq!(result.len(), 1);
+
+            let options = GlobalOptions::new(IdentityKey::All, true);
+            let result = find_conflicts(&sources, &jsons, &[], &options);
+            assert!(result.is_empty());
+        }
+    }
+
+    mod resolutions {
+        use super::*;
+
+        #[test]
+        fn dropping_versions() {
+            let (sources, jsons) = sample();
+            let mut conflicts = find_conflicts(&sources, &jsons, &[], &GlobalOptions::default());
+            conflicts[0].resolve(ConflictPolicy::Newest);
+
+            let options = GlobalOptions::default();
+            let resolutions = Resolutions::new(&conflicts, &options);
+            let result = resolutions.apply(&jsons);
+            assert_eq!(result[0].ghost_list().len(), 1);
+            assert_eq!(result[0].ghost_list()[0].directory(), "b");
+            assert_eq!(result[1].ghost_list().len(), 2);
+            assert!(result[1].ghost_list()[0].extra().is_empty());
+        }
+    }
+}
@@ -1,31 +1,52 @@
 use chrono::Utc;
 
 use crate::{
-    ast::{GhostData, GhostJson},
-    config::EraseArgs,
+    ast::{GhostData, GhostJson, Tombstone},
+    config::{EraseArgs, GlobalOptions},
     io::{load_json, write_json},
-    procedure::unique_fold,
+    procedure::{has_any_of, latest_tombstones, unique},
 };
 
-pub(super) fn erase(args: &EraseArgs) -> Result<(), std::io::Error> {
+pub(super) fn erase(args: &EraseArgs, options: &GlobalOptions) -> Result<(), std::io::Error> {
     let json = load_json(args.target())?;
 
-    let ghost_json = erase_body(args, &json);
+    let ghost_json = erase_body(args, &json, options);
 
-    write_json(args.target(), &ghost_json)
+    write_json(args.target(), &ghost_json, args.write_options())
 }
 
-fn erase_body(args: &EraseArgs, json: &GhostJson) -> GhostJson {
-    let ghost_list = json
-        .ghost_list()
-        .iter()
-        .filter_map(|v| erase_filter_map(v, args.directory(), args.sakura_name(), args.kero_name()))
-        .fold(Vec::new(), unique_fold);
-
-    println!("input raw length: {}", json.ghost_list().len());
-    println!("output length: {}", ghost_list.len());
-
-    GhostJson::new(Utc::now(), ghost_list)
+fn erase_body(args: &EraseArgs, json: &GhostJson, options: &GlobalOptions) -> GhostJson {
+    let ghost_list = unique(
+        json.ghost_list().iter().filter_map(|v| {
+            erase_filter_map(v, args.directory(), args.sakura_name(), args.kero_name())
+        }),
+        options,
+    );
+
+    eprintln!("input raw length: {}", json.ghost_list().len());
+    eprintln!("output length: {}", ghost_list.len());
+
+    let result = json.updated(ghost_list);
+
+    if args.tombstone() {
+        let erased_at = Utc::now();
+        let buried: Vec<_> = json
+            .ghost_list()
+            .iter()
+            .filter(|v| {
+                erase_filter_map(v, args.directory(), args.sakura_name(), args.kero_name())
+                    .is_none()
+            })
+            .map(|v| Tombstone::new(v, erased_at, args.reason().cloned()))
+            .collect();
+
+        eprintln!("tombstone length: {}", buried.len());
+
+        let tombstones = latest_tombstones(json.tombstones().iter().chain(buried.iter()));
+        result.with_tombstones(tombstones)
+    } else {
+        result
+    }
 }
 
 fn erase_filter_map<'a>(
@@ -34,16 +55,17 @@ fn erase_filter_map<'a>(
     sakura_name: Option<&String>,
     kero_name: Option<&String>,
 ) -> Option<&'a GhostData> {
-    match (directory, sakura_name, kero_name) {
-        (Some(d), _, _) if d == v.directory() => None,
-        (_, Some(s), _) if s == v.sakura_name() => None,
-        (_, _, Some(k)) if k == v.kero_name() => None,
-        _ => Some(v),
+    if has_any_of(v, directory, sakura_name, kero_name) {
+        None
+    } else {
+        Some(v)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::config::WriteOptions;
+
     use super::*;
 
     mod erase {
@@ -56,12 +78,19 @@ mod tests {
             let out_dir = tempdir().unwrap();
             let out_path = out_dir.path().join("test.json");
             let original_contents = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":""},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book"},{"directory":"tanumki","sakuraName":"きつね","keroName":"たぬき"},{"directory":"tcidelam","sakuraName":"シデラム","keroName":""}]}"#;
-            let original_json = serde_json::from_str(&original_contents).unwrap();
-            write_json(&out_path, &original_json).unwrap();
-
-            let args = EraseArgs::new(out_path.clone(), Some("100th_year".to_string()), None, None);
+            let original_json = serde_json::from_str(original_contents).unwrap();
+            write_json(&out_path, &original_json, &WriteOptions::default()).unwrap();
+
+            let args = EraseArgs::new(
+                out_path.clone(),
+                Some("100th_year".to_string()),
+                None,
+                None,
+                false,
+                None,
+            );
 
-            erase(&args).unwrap();
+            erase(&args, &GlobalOptions::default()).unwrap();
 
             let result = load_json(&out_path).unwrap();
             assert_eq!(
@@ -70,23 +99,51 @@ mod tests {
                     GhostData::new(
                         "FoxTheory".to_string(),
                         "リサ".to_string(),
-                        "book".to_string(),
+                        Some("book".to_string()),
                     ),
                     GhostData::new(
                         "tanumki".to_string(),
                         "きつね".to_string(),
-                        "たぬき".to_string(),
-                    ),
-                    GhostData::new(
-                        "tcidelam".to_string(),
-                        "シデラム".to_string(),
-                        "".to_string(),
+                        Some("たぬき".to_string()),
                     ),
+                    GhostData::new("tcidelam".to_string(), "シデラム".to_string(), None,),
                 ]
             );
 
             out_dir.close().unwrap();
         }
+
+        #[test]
+        fn keeping_unknown_fields() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.json");
+            let original_contents = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":""},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book","author":"tukinami"}],"source":"teammate"}"#;
+            let original_json = serde_json::from_str(original_contents).unwrap();
+            write_json(&out_path, &original_json, &WriteOptions::default()).unwrap();
+
+            let args = EraseArgs::new(
+                out_path.clone(),
+                Some("100th_year".to_string()),
+                None,
+                None,
+                false,
+                None,
+            );
+
+            erase(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(
+                result.extra().get("source"),
+                Some(&serde_json::Value::String("teammate".to_string()))
+            );
+            assert_eq!(
+                result.ghost_list()[0].extra().get("author"),
+                Some(&serde_json::Value::String("tukinami".to_string()))
+            );
+
+            out_dir.close().unwrap();
+        }
     }
 
     mod erase_body {
@@ -96,46 +153,57 @@ mod tests {
 
         #[test]
         fn erase_when_specified_value() {
-            let args = EraseArgs::new(PathBuf::new(), Some("100th_year".to_string()), None, None);
+            let args = EraseArgs::new(
+                PathBuf::new(),
+                Some("100th_year".to_string()),
+                None,
+                None,
+                false,
+                None,
+            );
             let json = GhostJson::new(
                 Utc::now(),
                 vec![
-                    GhostData::new("100th_year".to_string(), "霊".to_string(), "".to_string()),
+                    GhostData::new(
+                        "100th_year".to_string(),
+                        "霊".to_string(),
+                        Some("".to_string()),
+                    ),
                     GhostData::new(
                         "FoxTheory".to_string(),
                         "リサ".to_string(),
-                        "book".to_string(),
+                        Some("book".to_string()),
                     ),
                     GhostData::new(
                         "tanumki".to_string(),
                         "きつね".to_string(),
-                        "たぬき".to_string(),
+                        Some("たぬき".to_string()),
                     ),
                     GhostData::new(
                         "tcidelam".to_string(),
                         "シデラム".to_string(),
-                        "".to_string(),
+                        Some("".to_string()),
                     ),
                 ],
             );
-            let result = erase_body(&args, &json);
+            let result = erase_body(&args, &json, &GlobalOptions::default());
             assert_eq!(
                 result.ghost_list(),
                 &vec![
                     GhostData::new(
                         "FoxTheory".to_string(),
                         "リサ".to_string(),
-                        "book".to_string(),
+                        Some("book".to_string()),
                     ),
                     GhostData::new(
                         "tanumki".to_string(),
                         "きつね".to_string(),
-                        "たぬき".to_string(),
+                        Some("たぬき".to_string()),
                     ),
                     GhostData::new(
                         "tcidelam".to_string(),
                         "シデラム".to_string(),
-                        "".to_string(),
+                        Some("".to_string()),
                     ),
                 ]
             );
@@ -143,61 +211,134 @@ mod tests {
 
         #[test]
         fn not_erase_when_no_specified_value() {
-            let args = EraseArgs::new(PathBuf::new(), Some("d_invalid".to_string()), None, None);
+            let args = EraseArgs::new(
+                PathBuf::new(),
+                Some("d_invalid".to_string()),
+                None,
+                None,
+                false,
+                None,
+            );
             let json = GhostJson::new(
                 Utc::now(),
                 vec![
-                    GhostData::new("100th_year".to_string(), "霊".to_string(), "".to_string()),
+                    GhostData::new(
+                        "100th_year".to_string(),
+                        "霊".to_string(),
+                        Some("".to_string()),
+                    ),
                     GhostData::new(
                         "FoxTheory".to_string(),
                         "リサ".to_string(),
-                        "book".to_string(),
+                        Some("book".to_string()),
                     ),
                     GhostData::new(
                         "tanumki".to_string(),
                         "きつね".to_string(),
-                        "たぬき".to_string(),
+                        Some("たぬき".to_string()),
                     ),
                     GhostData::new(
                         "tcidelam".to_string(),
                         "シデラム".to_string(),
-                        "".to_string(),
+                        Some("".to_string()),
                     ),
                 ],
             );
-            let result = erase_body(&args, &json);
+            let result = erase_body(&args, &json, &GlobalOptions::default());
             assert_eq!(result.ghost_list(), json.ghost_list());
         }
 
         #[test]
         fn not_erase_when_nothing_value() {
-            let args = EraseArgs::new(PathBuf::new(), None, None, None);
+            let args = EraseArgs::new(PathBuf::new(), None, None, None, false, None);
             let json = GhostJson::new(
                 Utc::now(),
                 vec![
-                    GhostData::new("100th_year".to_string(), "霊".to_string(), "".to_string()),
+                    GhostData::new(
+                        "100th_year".to_string(),
+                        "霊".to_string(),
+                        Some("".to_string()),
+                    ),
                     GhostData::new(
                         "FoxTheory".to_string(),
                         "リサ".to_string(),
-                        "book".to_string(),
+                        Some("book".to_string()),
                     ),
                     GhostData::new(
                         "tanumki".to_string(),
                         "きつね".to_string(),
-                        "たぬき".to_string(),
+                        Some("たぬき".to_string()),
                     ),
                     GhostData::new(
                         "tcidelam".to_string(),
                         "シデラム".to_string(),
-                        "".to_string(),
+                        Some("".to_string()),
                     ),
                 ],
             );
-            let result = erase_body(&args, &json);
+            let result = erase_body(&args, &json, &GlobalOptions::default());
             assert_eq!(result.ghost_list(), json.ghost_list());
         }
     }
 
+    mod tombstone {
+        use std::path::PathBuf;
+
+        use super::*;
+
+        #[test]
+        fn recording_erased_ghosts() {
+            let args = EraseArgs::new(
+                PathBuf::new(),
+                None,
+                Some("霊".to_string()),
+                None,
+                true,
+                Some("bogus".to_string()),
+            );
+            let erased = GhostData::new("100th_year".to_string(), "霊".to_string(), None);
+            let json = GhostJson::new(
+                Utc::now(),
+                vec![
+                    erased.clone(),
+                    GhostData::new(
+                        "FoxTheory".to_string(),
+                        "リサ".to_string(),
+                        Some("book".to_string()),
+                    ),
+                ],
+            );
+            let result = erase_body(&args, &json, &GlobalOptions::default());
+            assert_eq!(result.ghost_list().len(), 1);
+            assert_eq!(result.tombstones().len(), 1);
+            assert_eq!(result.tombstones()[0].ghost(), &erased);
+            assert_eq!(result.tombstones()[0].reason(), Some(&"bogus".to_string()));
+        }
+
+        #[test]
+        fn not_recording_without_flag() {
+            let args = EraseArgs::new(
+                PathBuf::new(),
+                None,
+                Some("霊".to_string()),
+                None,
+                false,
+                None,
+            );
+            let json = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new(
+                    "100th_year".to_string(),
+                    "霊".to_string(),
+                    None,
+                )],
+            );
+            let result = erase_body(&args, &json, &GlobalOptions::default());
+            assert!(result.ghost_list().is_empty());
+            assert!(result.tombstones().is_empty());
+        }
+    }
+
     mod erase_filter_map {
         use super::*;
 
@@ -206,7 +347,7 @@ mod tests {
             let v = GhostData::new(
                 "d_valid".to_string(),
                 "s_valid".to_string(),
-                "k_valid".to_string(),
+                Some("k_valid".to_string()),
             );
             let directory = Some("d_valid".to_string());
             let sakura_name = None;
@@ -250,12 +391,20 @@ mod tests {
             assert!(result.is_none());
         }
 
+        #[test]
+        fn none_when_same_directory_on_windows() {
+            let v = GhostData::new("Emily".to_string(), "s".to_string(), None);
+            let directory = Some("emily.".to_string());
+            let result = erase_filter_map(&v, directory.as_ref(), None, None);
+            assert!(result.is_none());
+        }
+
         #[test]
         fn none_when_same_sakura_name() {
             let v = GhostData::new(
                 "d_valid".to_string(),
                 "s_valid".to_string(),
-                "k_valid".to_string(),
+                Some("k_valid".to_string()),
             );
             let directory = None;
             let sakura_name = Some("s_valid".to_string());
@@ -294,7 +443,7 @@ mod tests {
             let v = GhostData::new(
                 "d_valid".to_string(),
                 "s_valid".to_string(),
-                "k_valid".to_string(),
+                Some("k_valid".to_string()),
             );
             let directory = None;
             let sakura_name = None;
@@ -333,7 +482,7 @@ mod tests {
             let v = GhostData::new(
                 "d_valid".to_string(),
                 "s_valid".to_string(),
-                "k_valid".to_string(),
+                Some("k_valid".to_string()),
             );
             let directory = Some("d_invalid".to_string());
             let sakura_name = Some("s_invalid".to_string());
@@ -347,12 +496,20 @@ mod tests {
             assert_eq!(result, Some(&v));
         }
 
+        #[test]
+        fn some_value_when_blank_kero_name_and_no_kero() {
+            let v = GhostData::new("d_valid".to_string(), "s_valid".to_string(), None);
+            let kero_name = Some("".to_string());
+            let result = erase_filter_map(&v, None, None, kero_name.as_ref());
+            assert_eq!(result, Some(&v));
+        }
+
         #[test]
         fn some_value_when_all_none() {
             let v = GhostData::new(
                 "d_valid".to_string(),
                 "s_valid".to_string(),
-                "k_valid".to_string(),
+                Some("k_valid".to_string()),
             );
             let directory = None;
             let sakura_name = None;
@@ -0,0 +1,205 @@
+use crate::{
+    ast::GhostJson,
+    config::{ExportArgs, ExportFormat, TextEncoding},
+    database::write_database,
+    io::{charset_of, load_json, unmappable_chars, write_encoded_text},
+    report::{name_list, write_html, write_markdown},
+    table::write_table,
+    typescript::write_typescript,
+};
+
+pub(super) fn export(args: &ExportArgs) -> Result<(), std::io::Error> {
+    let json = load_json(args.input())?;
+
+    let contents = match args.format() {
+        ExportFormat::Csv => write_table(&json, b',')?,
+        ExportFormat::Tsv => write_table(&json, b'\t')?,
+        ExportFormat::Html => write_html(&json, args.group(), charset_of(args.encoding())),
+        ExportFormat::Markdown => write_markdown(&json, args.group()),
+        ExportFormat::Sqlite => {
+            eprintln!("exported length: {}", json.ghost_list().len());
+            return write_database(args.output(), &json);
+        }
+        ExportFormat::Text => write_names(&json, args)?,
+        ExportFormat::Typescript => {
+            // typescript compilers read sources as UTF-8.
+            if args.encoding() != TextEncoding::Utf8 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "typescript is written only in utf-8",
+                ));
+            }
+            write_typescript(&json)?
+        }
+    };
+
+    eprintln!("exported length: {}", json.ghost_list().len());
+
+    write_encoded_text(args.output(), &contents, args.encoding())
+}
+
+/// one name per line. names which the encoding cannot represent are reported as an error.
+fn write_names(json: &GhostJson, args: &ExportArgs) -> Result<String, std::io::Error> {
+    let names = name_list(json, args.field());
+
+    let mut unrepresentable = 0;
+    for name in names.iter() {
+        let chars = unmappable_chars(name, args.encoding());
+        if !chars.is_empty() {
+            eprintln!(
+                "not representable: {} ({})",
+                name,
+                chars.iter().collect::<String>()
+            );
+            unrepresentable += 1;
+        }
+    }
+    if unrepresentable > 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "{} name(s) not representable in the encoding",
+                unrepresentable
+            ),
+        ));
+    }
+
+    Ok(names.iter().map(|v| format!("{}\n", v)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod export {
+        use std::path::PathBuf;
+
+        use tempfile::tempdir;
+
+        use crate::{
+            config::{NameField, TextEncoding},
+            io::load_encoded_text,
+        };
+
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.tsv");
+
+            let input = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("test_target/json/valid/Konuka.json");
+            let args = ExportArgs::new(
+                input,
+                out_path.clone(),
+                ExportFormat::Tsv,
+                TextEncoding::ShiftJis,
+                false,
+                NameField::All,
+            );
+
+            export(&args).unwrap();
+
+            let result = load_encoded_text(&out_path, TextEncoding::ShiftJis).unwrap();
+            assert_eq!(
+                result,
+                "directory\tsakuraName\tkeroName\tnoKero\n\
+                 100th_year\t霊\t\ttrue\n\
+                 FoxTheory\tリサ\tbook\t\n\
+                 tanumki\tきつね\tたぬき\t\n\
+                 tcidelam\tシデラム\t\ttrue\n"
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn writing_names_in_legacy_encoding() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.txt");
+
+            let input = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("test_target/json/valid/Konuka.json");
+            let args = ExportArgs::new(
+                input,
+                out_path.clone(),
+                ExportFormat::Text,
+                TextEncoding::ShiftJis,
+                false,
+                NameField::Kero,
+            );
+
+            export(&args).unwrap();
+
+            let result = load_encoded_text(&out_path, TextEncoding::ShiftJis).unwrap();
+            assert_eq!(result, "book\nたぬき\n");
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_name_is_not_representable() {
+            let out_dir = tempdir().unwrap();
+            let in_path = out_dir.path().join("test.json");
+            let out_path = out_dir.path().join("test.txt");
+            std::fs::write(
+                &in_path,
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"a","sakuraName":"𠮷野","keroName":""}]}"#,
+            )
+            .unwrap();
+
+            let args = ExportArgs::new(
+                in_path,
+                out_path.clone(),
+                ExportFormat::Text,
+                TextEncoding::ShiftJis,
+                false,
+                NameField::Sakura,
+            );
+
+            assert!(export(&args).is_err());
+            assert!(!out_path.exists());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn declaring_encoding_of_html() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.html");
+
+            let input = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("test_target/json/valid/Konuka.json");
+            let args = ExportArgs::new(
+                input.clone(),
+                out_path.clone(),
+                ExportFormat::Html,
+                TextEncoding::ShiftJis,
+                false,
+                NameField::All,
+            );
+
+            export(&args).unwrap();
+
+            let result = load_encoded_text(&out_path, TextEncoding::ShiftJis).unwrap();
+            assert!(result.contains("<meta charset=\"Shift_JIS\">"));
+            assert!(result.contains("<td>リサ</td>"));
+
+            let out_path = out_dir.path().join("test.ts");
+            let args = ExportArgs::new(
+                input,
+                out_path.clone(),
+                ExportFormat::Typescript,
+                TextEncoding::ShiftJis,
+                false,
+                NameField::All,
+            );
+
+            assert!(export(&args).is_err());
+            assert!(!out_path.exists());
+
+            out_dir.close().unwrap();
+        }
+    }
+}
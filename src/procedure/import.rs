@@ -0,0 +1,164 @@
+use chrono::Utc;
+
+use crate::{
+    ast::GhostJson,
+    config::{GlobalOptions, ImportArgs, ImportFormat},
+    database::read_database,
+    io::{load_encoded_text, write_json},
+    procedure::unique,
+    table::read_table,
+};
+
+pub(super) fn import(args: &ImportArgs, options: &GlobalOptions) -> Result<(), std::io::Error> {
+    let json = match args.format() {
+        ImportFormat::Csv => read_table_file(args, b',')?,
+        ImportFormat::Tsv => read_table_file(args, b'\t')?,
+        ImportFormat::Sqlite => read_database(args.input())?,
+    };
+
+    eprintln!("raw length: {}", json.ghost_list().len());
+
+    let ghost_list = unique(json.ghost_list(), options);
+
+    eprintln!("result length: {}", ghost_list.len());
+
+    let json = json.with_ghost_list(ghost_list);
+
+    write_json(args.output(), &json, args.write_options())
+}
+
+fn read_table_file(args: &ImportArgs, delimiter: u8) -> Result<GhostJson, std::io::Error> {
+    let contents = load_encoded_text(args.input(), args.encoding())?;
+    let ghost_list = read_table(&contents, delimiter)?;
+
+    Ok(GhostJson::new(Utc::now(), ghost_list))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod import {
+        use std::path::PathBuf;
+
+        use tempfile::tempdir;
+
+        use crate::{
+            ast::GhostData,
+            config::{
+                AppendArgs, ConflictPolicy, ExportArgs, ExportFormat, NameField, TextEncoding,
+                WriteOptions,
+            },
+            io::{load_json, write_encoded_text},
+            procedure::{append::append, export::export},
+        };
+
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let out_dir = tempdir().unwrap();
+            let csv_path = out_dir.path().join("test.csv");
+            let out_path = out_dir.path().join("test.json");
+
+            write_encoded_text(
+                &csv_path,
+                "directory,sakuraName,keroName\n\
+                 100th_year,霊,\n\
+                 FoxTheory,\"リサ, the fox\",book\n\
+                 FoxTheory,\"リサ, the fox\",book\n",
+                TextEncoding::ShiftJis,
+            )
+            .unwrap();
+
+            let args = ImportArgs::new(
+                csv_path,
+                out_path.clone(),
+                ImportFormat::Csv,
+                TextEncoding::ShiftJis,
+            );
+
+            import(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(
+                result.ghost_list(),
+                &vec![
+                    GhostData::new("100th_year".to_string(), "霊".to_string(), None),
+                    GhostData::new(
+                        "FoxTheory".to_string(),
+                        "リサ, the fox".to_string(),
+                        Some("book".to_string())
+                    ),
+                ]
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn round_trip_through_export_and_append() {
+            let out_dir = tempdir().unwrap();
+            let json_path = out_dir.path().join("test.json");
+            let csv_path = out_dir.path().join("test.csv");
+            let imported_path = out_dir.path().join("imported.json");
+            let input =
+                vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/ghost/valid")];
+
+            let json = GhostJson::new(
+                Utc::now(),
+                vec![
+                    GhostData::new(
+                        "aaa".to_string(),
+                        "さくらAAA".to_string(),
+                        Some("ケロAAA".to_string()),
+                    ),
+                    GhostData::new("bbb".to_string(), "さくらBBB".to_string(), None),
+                    GhostData::new(
+                        "zzz".to_string(),
+                        "さくらZZZ".to_string(),
+                        Some("".to_string()),
+                    ),
+                ],
+            );
+            write_json(&json_path, &json, &WriteOptions::default()).unwrap();
+
+            let export_args = ExportArgs::new(
+                json_path,
+                csv_path.clone(),
+                ExportFormat::Csv,
+                TextEncoding::Utf8,
+                false,
+                NameField::All,
+            );
+            export(&export_args).unwrap();
+
+            let import_args = ImportArgs::new(
+                csv_path,
+                imported_path.clone(),
+                ImportFormat::Csv,
+                TextEncoding::Utf8,
+            );
+            import(&import_args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&imported_path).unwrap();
+            assert_eq!(result.ghost_list(), json.ghost_list());
+
+            let append_args = AppendArgs::new(
+                input,
+                imported_path.clone(),
+                ConflictPolicy::KeepAll,
+                false,
+                false,
+            );
+            append(&append_args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&imported_path).unwrap();
+            let directories: Vec<&String> =
+                result.ghost_list().iter().map(|v| v.directory()).collect();
+            assert_eq!(directories, vec!["aaa", "bbb", "zzz", "ccc"]);
+
+            out_dir.close().unwrap();
+        }
+    }
+}
@@ -1,44 +1,248 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
 use chrono::Utc;
 
 use crate::{
-    ast::GhostJson,
-    config::MergeArgs,
-    io::{load_json, write_json},
-    procedure::unique_fold,
+    ast::{directory_key, GhostJson},
+    config::{ConflictPolicy, GlobalOptions, MergeArgs},
+    io::{list_files, load_json, salvage_json, write_json, write_text},
+    procedure::{erased_times, is_buried, key_of, latest_tombstones, unique, Key},
 };
 
-pub(super) fn merge(args: &MergeArgs) -> Result<(), std::io::Error> {
+use super::conflict::{find_conflicts, Resolutions};
+
+pub(super) fn merge(args: &MergeArgs, options: &GlobalOptions) -> Result<(), std::io::Error> {
+    let mut sources = Vec::new();
     let mut jsons = Vec::new();
-    for p in args.input().iter() {
-        match load_json(p) {
-            Ok(v) => jsons.push(v),
+    // the output is left out of directories and patterns, but read when given explicitly.
+    for p in list_files(args.input(), args.recursive(), Some(args.output()))?.iter() {
+        let loaded = if args.lenient() {
+            salvage_json(p)
+        } else {
+            load_json(p).map(|v| (v, Vec::new()))
+        };
+        match loaded {
+            Ok((v, problems)) => {
+                for problem in problems.iter() {
+                    eprintln!("{}:{}", p.display(), problem);
+                }
+                eprintln!("used: {}", p.display());
+                sources.push(p.display().to_string());
+                jsons.push(v);
+            }
             Err(e) => {
-                eprintln!("{}: {}", p.display(), e);
+                eprintln!("skipped: {}: {}", p.display(), e);
             }
         }
     }
 
-    let json = merge_body(&jsons);
+    if args.policy() == ConflictPolicy::Scanned {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "policy `scanned` is only for append",
+        ));
+    }
+
+    let base = match args.base() {
+        Some(p) => Some(load_json(p)?),
+        None => None,
+    };
+
+    let tombstones = latest_tombstones(jsons.iter().flat_map(|v| v.tombstones().iter()));
+    let mut conflicts = match (args.base(), &base) {
+        (Some(path), Some(base)) => {
+            // only changes from the base conflict. the base comes last not to be preferred.
+            let directories = diverged_directories(base, &jsons, options);
+            let mut sources = sources.clone();
+            sources.push(path.display().to_string());
+            let inputs: Vec<GhostJson> = jsons
+                .iter()
+                .chain([base])
+                .map(|v| only_directories(v, &directories))
+                .collect();
+            find_conflicts(&sources, &inputs, &tombstones, options)
+        }
+        _ => find_conflicts(&sources, &jsons, &tombstones, options),
+    };
+    for c in conflicts.iter_mut() {
+        c.resolve(args.policy());
+        eprintln!("{}", c);
+    }
+    if let Some(path) = args.conflict_report() {
+        let report = serde_json::json!({ "conflicts": conflicts });
+        write_text(path, &serde_json::to_string_pretty(&report)?)?;
+    }
+    let unresolved = conflicts.iter().filter(|c| !c.is_resolved()).count();
+    if args.fail_on_conflict() && unresolved > 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} conflict(s) found", unresolved),
+        ));
+    }
 
-    write_json(args.output(), &json)
+    let resolutions = Resolutions::new(&conflicts, options);
+    let inputs = match &base {
+        Some(base) => {
+            let mut inputs = drop_removed(base, &jsons, options);
+            // a version which only the base has may be kept by the policy.
+            let kept = base
+                .ghost_list()
+                .iter()
+                .filter(|v| resolutions.is_kept(v))
+                .cloned()
+                .collect();
+            inputs.push(base.clone().with_ghost_list(kept));
+            inputs
+        }
+        None => jsons,
+    };
+    let json = merge_body(&resolutions.apply(&inputs), options);
+
+    write_json(args.output(), &json, args.write_options())
 }
 
-fn merge_body(jsons: &[GhostJson]) -> GhostJson {
-    let ghost_list_iter = jsons.iter().flat_map(|v| v.ghost_list().iter());
-    let old_size_hint = ghost_list_iter.clone().size_hint();
-    let old_size = old_size_hint.1.unwrap_or(old_size_hint.0);
+/// directories which `jsons` changed from `base` in different ways, compared by `directory_key`.
+fn diverged_directories(
+    base: &GhostJson,
+    jsons: &[GhostJson],
+    options: &GlobalOptions,
+) -> HashSet<String> {
+    let base_keys: HashSet<Key> = base
+        .ghost_list()
+        .iter()
+        .map(|v| key_of(v, options))
+        .collect();
+
+    // for each directory, sorted pairs of (added, key) which each json changed.
+    let mut changes: HashMap<Cow<str>, Vec<Vec<(bool, Key)>>> = HashMap::new();
+    for json in jsons.iter() {
+        let keys: HashSet<Key> = json
+            .ghost_list()
+            .iter()
+            .map(|v| key_of(v, options))
+            .collect();
 
-    println!("input raw length: {}", old_size);
+        let mut change: BTreeMap<Cow<str>, Vec<(bool, Key)>> = BTreeMap::new();
+        for v in base.ghost_list().iter() {
+            if !keys.contains(&key_of(v, options)) {
+                change
+                    .entry(directory_key(v.directory()))
+                    .or_default()
+                    .push((false, key_of(v, options)));
+            }
+        }
+        for v in json.ghost_list().iter() {
+            if !base_keys.contains(&key_of(v, options)) {
+                change
+                    .entry(directory_key(v.directory()))
+                    .or_default()
+                    .push((true, key_of(v, options)));
+            }
+        }
 
-    let ghost_list = ghost_list_iter.fold(Vec::new(), unique_fold);
+        for (directory, mut c) in change {
+            c.sort();
+            c.dedup();
+            changes.entry(directory).or_default().push(c);
+        }
+    }
 
-    println!("output length: {}", ghost_list.len());
+    changes
+        .into_iter()
+        .filter(|(_, c)| c.iter().any(|v| v != &c[0]))
+        .map(|(directory, _)| directory.to_string())
+        .collect()
+}
+
+/// copy of `json` with entries in `directories` only. `directories` are given by `directory_key`.
+fn only_directories(json: &GhostJson, directories: &HashSet<String>) -> GhostJson {
+    let ghost_list = json
+        .ghost_list()
+        .iter()
+        .filter(|v| directories.contains(directory_key(v.directory()).as_ref()))
+        .cloned()
+        .collect();
+    json.clone().with_ghost_list(ghost_list)
+}
+
+/// copies of `jsons` without entries of `base` which any of them removed.
+fn drop_removed(base: &GhostJson, jsons: &[GhostJson], options: &GlobalOptions) -> Vec<GhostJson> {
+    let mut removed: HashSet<Key> = HashSet::new();
+    for json in jsons.iter() {
+        let keys: HashSet<Key> = json
+            .ghost_list()
+            .iter()
+            .map(|v| key_of(v, options))
+            .collect();
+        removed.extend(
+            base.ghost_list()
+                .iter()
+                .map(|v| key_of(v, options))
+                .filter(|k| !keys.contains(k)),
+        );
+    }
+
+    jsons
+        .iter()
+        .map(|json| {
+            let ghost_list = json
+                .ghost_list()
+                .iter()
+                .filter(|v| !removed.contains(&key_of(v, options)))
+                .cloned()
+                .collect();
+            json.clone().with_ghost_list(ghost_list)
+        })
+        .collect()
+}
+
+fn merge_body(jsons: &[GhostJson], options: &GlobalOptions) -> GhostJson {
+    let old_size: usize = jsons.iter().map(|v| v.ghost_list().len()).sum();
+
+    eprintln!("input raw length: {}", old_size);
+
+    let mut tombstones = latest_tombstones(jsons.iter().flat_map(|v| v.tombstones().iter()));
+    let erased = erased_times(&tombstones);
+
+    let mut alive = Vec::new();
+    for json in jsons.iter() {
+        let update = json.update_time();
+        alive.extend(
+            json.ghost_list()
+                .iter()
+                .filter(|v| !is_buried(&erased, v, update)),
+        );
+    }
+
+    eprintln!("buried length: {}", old_size - alive.len());
+
+    let ghost_list = unique(alive, options);
+
+    // entries which came back after erasing are no longer buried.
+    let resurrected: HashSet<_> = ghost_list.iter().map(|v| v.key()).collect();
+    tombstones.retain(|t| !resurrected.contains(&t.ghost().key()));
+
+    eprintln!("output length: {}", ghost_list.len());
+
+    let mut extra = serde_json::Map::new();
+    for (k, v) in jsons.iter().flat_map(|v| v.extra().iter()) {
+        if !extra.contains_key(k) {
+            extra.insert(k.clone(), v.clone());
+        }
+    }
 
     GhostJson::new(Utc::now(), ghost_list)
+        .with_tombstones(tombstones)
+        .with_extra(extra)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::config::{IdentityKey, WriteOptions};
+
     use super::*;
 
     mod merge {
@@ -58,10 +262,10 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
-            write_json(&json_a_path, &json_a).unwrap();
+            write_json(&json_a_path, &json_a, &WriteOptions::default()).unwrap();
 
             let json_b_path = out_dir.path().join("json_b.json");
             let json_b = GhostJson::new(
@@ -69,10 +273,10 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
-            write_json(&json_b_path, &json_b).unwrap();
+            write_json(&json_b_path, &json_b, &WriteOptions::default()).unwrap();
 
             let json_c_path = out_dir.path().join("json_c.json");
             let json_c = GhostJson::new(
@@ -80,10 +284,10 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
-            write_json(&json_c_path, &json_c).unwrap();
+            write_json(&json_c_path, &json_c, &WriteOptions::default()).unwrap();
 
             let out_path = out_dir.path().join("test.json");
             let args = MergeArgs::new(
@@ -92,10 +296,15 @@ mod tests {
                     json_b_path.clone(),
                     json_c_path.clone(),
                 ],
+                false,
                 out_path.clone(),
+                None,
+                None,
+                false,
+                ConflictPolicy::KeepAll,
             );
 
-            merge(&args).unwrap();
+            merge(&args, &GlobalOptions::default()).unwrap();
 
             let result = load_json(&out_path).unwrap();
             assert_eq!(
@@ -103,19 +312,483 @@ mod tests {
                 &vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )]
             );
 
             out_dir.close().unwrap();
         }
+
+        #[test]
+        fn failed_with_report_when_conflicts() {
+            let out_dir = tempdir().unwrap();
+
+            let json_a_path = out_dir.path().join("json_a.json");
+            let json_a = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("a_d".to_string(), "a_s".to_string(), None)],
+            );
+            write_json(&json_a_path, &json_a, &WriteOptions::default()).unwrap();
+
+            let json_b_path = out_dir.path().join("json_b.json");
+            let json_b = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("a_d".to_string(), "b_s".to_string(), None)],
+            );
+            write_json(&json_b_path, &json_b, &WriteOptions::default()).unwrap();
+
+            let out_path = out_dir.path().join("test.json");
+            let report_path = out_dir.path().join("conflicts.json");
+            let args = MergeArgs::new(
+                vec![json_a_path, json_b_path.clone()],
+                false,
+                out_path.clone(),
+                None,
+                Some(report_path.clone()),
+                true,
+                ConflictPolicy::KeepAll,
+            );
+
+            assert!(merge(&args, &GlobalOptions::default()).is_err());
+            assert!(!out_path.exists());
+
+            let report: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+            assert_eq!(report["conflicts"][0]["directory"], "a_d");
+            assert_eq!(report["conflicts"][0]["versions"][1]["sakuraName"], "b_s");
+            assert_eq!(
+                report["conflicts"][0]["versions"][1]["sources"][0],
+                json_b_path.display().to_string()
+            );
+            assert!(report["conflicts"][0]["versions"][1]["keroName"].is_null());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn collapsing_same_directory_when_key_is_directory() {
+            let out_dir = tempdir().unwrap();
+
+            let json_a_path = out_dir.path().join("json_a.json");
+            let json_a = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new(
+                    "a_d".to_string(),
+                    "a_s".to_string(),
+                    Some("a_k".to_string()),
+                )],
+            );
+            write_json(&json_a_path, &json_a, &WriteOptions::default()).unwrap();
+
+            let json_b_path = out_dir.path().join("json_b.json");
+            let json_b = GhostJson::new(
+                Utc::now(),
+                vec![
+                    GhostData::new("a_d".to_string(), "renamed".to_string(), None),
+                    GhostData::new(
+                        "b_d".to_string(),
+                        "a_s".to_string(),
+                        Some("a_k".to_string()),
+                    ),
+                ],
+            );
+            write_json(&json_b_path, &json_b, &WriteOptions::default()).unwrap();
+
+            let out_path = out_dir.path().join("test.json");
+            let args = MergeArgs::new(
+                vec![json_a_path, json_b_path],
+                false,
+                out_path.clone(),
+                None,
+                None,
+                false,
+                ConflictPolicy::KeepAll,
+            );
+
+            merge(&args, &GlobalOptions::new(IdentityKey::Directory, false)).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(
+                result.ghost_list(),
+                &vec![
+                    GhostData::new(
+                        "a_d".to_string(),
+                        "a_s".to_string(),
+                        Some("a_k".to_string())
+                    ),
+                    GhostData::new(
+                        "b_d".to_string(),
+                        "a_s".to_string(),
+                        Some("a_k".to_string())
+                    ),
+                ]
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn resolving_conflicts_by_newest() {
+            let out_dir = tempdir().unwrap();
+
+            let json_a_path = out_dir.path().join("json_a.json");
+            let json_a = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("a_d".to_string(), "a_s".to_string(), None)],
+            );
+            write_json(&json_a_path, &json_a, &WriteOptions::default()).unwrap();
+
+            let json_b_path = out_dir.path().join("json_b.json");
+            let json_b = GhostJson::new(
+                Utc::now() - chrono::Duration::days(1),
+                vec![GhostData::new("a_d".to_string(), "b_s".to_string(), None)],
+            );
+            write_json(&json_b_path, &json_b, &WriteOptions::default()).unwrap();
+
+            let out_path = out_dir.path().join("test.json");
+            let report_path = out_dir.path().join("conflicts.json");
+            let args = MergeArgs::new(
+                vec![json_b_path, json_a_path],
+                false,
+                out_path.clone(),
+                None,
+                Some(report_path.clone()),
+                true,
+                ConflictPolicy::Newest,
+            );
+
+            merge(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(result.ghost_list().len(), 1);
+            assert_eq!(result.ghost_list()[0].sakura_name(), "a_s");
+            assert!(result.ghost_list()[0].extra().is_empty());
+
+            let report: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+            assert_eq!(report["conflicts"][0]["resolution"], "newest");
+            assert_eq!(report["conflicts"][0]["versions"][0]["kept"], false);
+            assert_eq!(report["conflicts"][0]["versions"][1]["kept"], true);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_policy_is_scanned() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.json");
+            let args = MergeArgs::new(
+                vec![],
+                false,
+                out_path.clone(),
+                None,
+                None,
+                false,
+                ConflictPolicy::Scanned,
+            );
+
+            assert!(merge(&args, &GlobalOptions::default()).is_err());
+            assert!(!out_path.exists());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn merging_changes_from_base() {
+            let out_dir = tempdir().unwrap();
+
+            let base_path = out_dir.path().join("base.json");
+            let base = GhostJson::new(
+                Utc::now() - chrono::Duration::days(1),
+                vec![
+                    GhostData::new("a_d".to_string(), "a_s".to_string(), None),
+                    GhostData::new("b_d".to_string(), "b_s".to_string(), None),
+                    GhostData::new("c_d".to_string(), "c_s".to_string(), None),
+                ],
+            );
+            write_json(&base_path, &base, &WriteOptions::default()).unwrap();
+
+            // renames a_d and adds d_d.
+            let json_a_path = out_dir.path().join("json_a.json");
+            let json_a = GhostJson::new(
+                Utc::now(),
+                vec![
+                    GhostData::new("a_d".to_string(), "renamed_a".to_string(), None),
+                    GhostData::new("b_d".to_string(), "b_s".to_string(), None),
+                    GhostData::new("c_d".to_string(), "c_s".to_string(), None),
+                    GhostData::new("d_d".to_string(), "d_s".to_string(), None),
+                ],
+            );
+            write_json(&json_a_path, &json_a, &WriteOptions::default()).unwrap();
+
+            // renames a_d differently and removes b_d.
+            let json_b_path = out_dir.path().join("json_b.json");
+            let json_b = GhostJson::new(
+                Utc::now(),
+                vec![
+                    GhostData::new("a_d".to_string(), "renamed_b".to_string(), None),
+                    GhostData::new("c_d".to_string(), "c_s".to_string(), None),
+                ],
+            );
+            write_json(&json_b_path, &json_b, &WriteOptions::default()).unwrap();
+
+            let out_path = out_dir.path().join("test.json");
+            let report_path = out_dir.path().join("conflicts.json");
+            let args = MergeArgs::new(
+                vec![json_a_path.clone(), json_b_path.clone()],
+                false,
+                out_path.clone(),
+                Some(base_path.clone()),
+                Some(report_path.clone()),
+                false,
+                ConflictPolicy::KeepAll,
+            );
+
+            merge(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(
+                result.ghost_list(),
+                &vec![
+                    GhostData::new("a_d".to_string(), "renamed_a".to_string(), None),
+                    GhostData::new("c_d".to_string(), "c_s".to_string(), None),
+                    GhostData::new("d_d".to_string(), "d_s".to_string(), None),
+                    GhostData::new("a_d".to_string(), "renamed_b".to_string(), None),
+                ]
+            );
+
+            let report: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+            let conflicts = report["conflicts"].as_array().unwrap();
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0]["directory"], "a_d");
+            assert_eq!(conflicts[0]["versions"][2]["sakuraName"], "a_s");
+            assert_eq!(
+                conflicts[0]["versions"][2]["sources"][0],
+                base_path.display().to_string()
+            );
+
+            let args = MergeArgs::new(
+                vec![json_a_path, json_b_path],
+                false,
+                out_path.clone(),
+                Some(base_path),
+                None,
+                true,
+                ConflictPolicy::Priority,
+            );
+
+            merge(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(result.ghost_list().len(), 3);
+            assert_eq!(result.ghost_list()[0].sakura_name(), "renamed_a");
+            assert!(result.ghost_list()[0].extra().is_empty());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn taking_jsons_in_directory() {
+            let out_dir = tempdir().unwrap();
+
+            let json_a_path = out_dir.path().join("json_a.json");
+            let json_a = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("a_d".to_string(), "a_s".to_string(), None)],
+            );
+            write_json(&json_a_path, &json_a, &WriteOptions::default()).unwrap();
+
+            let json_b_path = out_dir.path().join("json_b.json");
+            let json_b = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("b_d".to_string(), "b_s".to_string(), None)],
+            );
+            write_json(&json_b_path, &json_b, &WriteOptions::default()).unwrap();
+
+            // the output in the directory is not taken as an input.
+            let out_path = out_dir.path().join("merged.json");
+            let stale = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("c_d".to_string(), "c_s".to_string(), None)],
+            );
+            write_json(&out_path, &stale, &WriteOptions::default()).unwrap();
+
+            let args = MergeArgs::new(
+                vec![out_dir.path().to_path_buf()],
+                false,
+                out_path.clone(),
+                None,
+                None,
+                false,
+                ConflictPolicy::KeepAll,
+            );
+
+            merge(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(
+                result.ghost_list(),
+                &vec![
+                    GhostData::new("a_d".to_string(), "a_s".to_string(), None),
+                    GhostData::new("b_d".to_string(), "b_s".to_string(), None),
+                ]
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn taking_output_given_explicitly() {
+            let out_dir = tempdir().unwrap();
+
+            let out_path = out_dir.path().join("ghost_list.json");
+            let json_a = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("a_d".to_string(), "a_s".to_string(), None)],
+            );
+            write_json(&out_path, &json_a, &WriteOptions::default()).unwrap();
+
+            let json_b_path = out_dir.path().join("json_b.json");
+            let json_b = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("b_d".to_string(), "b_s".to_string(), None)],
+            );
+            write_json(&json_b_path, &json_b, &WriteOptions::default()).unwrap();
+
+            let args = MergeArgs::new(
+                vec![out_path.clone(), json_b_path.clone()],
+                false,
+                out_path.clone(),
+                None,
+                None,
+                false,
+                ConflictPolicy::KeepAll,
+            );
+
+            merge(&args, &GlobalOptions::default()).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(
+                result.ghost_list(),
+                &vec![
+                    GhostData::new("a_d".to_string(), "a_s".to_string(), None),
+                    GhostData::new("b_d".to_string(), "b_s".to_string(), None),
+                ]
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn salvaging_damaged_input_when_lenient() {
+            let out_dir = tempdir().unwrap();
+
+            let json_a_path = out_dir.path().join("json_a.json");
+            let json_a = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("a_d".to_string(), "a_s".to_string(), None)],
+            );
+            write_json(&json_a_path, &json_a, &WriteOptions::default()).unwrap();
+
+            let json_b_path = out_dir.path().join("json_b.json");
+            std::fs::write(
+                &json_b_path,
+                r#"{"ghostList":[{"directory":"b_d","sakuraName":1},{"directory":"c_d","sakuraName":"c_s","keroName":"","blankKero":true}]}"#,
+            )
+            .unwrap();
+
+            let out_path = out_dir.path().join("test.json");
+            let args = MergeArgs::new(
+                vec![json_a_path.clone(), json_b_path.clone()],
+                false,
+                out_path.clone(),
+                None,
+                None,
+                false,
+                ConflictPolicy::KeepAll,
+            );
+
+            merge(&args, &GlobalOptions::default()).unwrap();
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(result.ghost_list().len(), 1);
+
+            merge(&args.with_lenient(true), &GlobalOptions::default()).unwrap();
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(
+                result.ghost_list(),
+                &vec![
+                    GhostData::new("a_d".to_string(), "a_s".to_string(), None),
+                    GhostData::new("c_d".to_string(), "c_s".to_string(), Some(String::new())),
+                ]
+            );
+
+            out_dir.close().unwrap();
+        }
     }
 
-    mod merge_body {
+    mod diverged_directories {
         use crate::ast::GhostData;
 
         use super::*;
 
+        #[test]
+        fn ignoring_same_changes() {
+            let base = GhostJson::new(
+                Utc::now(),
+                vec![
+                    GhostData::new("a_d".to_string(), "a_s".to_string(), None),
+                    GhostData::new("b_d".to_string(), "b_s".to_string(), None),
+                ],
+            );
+            let json_a = GhostJson::new(
+                Utc::now(),
+                vec![
+                    GhostData::new("a_d".to_string(), "renamed".to_string(), None),
+                    GhostData::new("c_d".to_string(), "c_s".to_string(), None),
+                ],
+            );
+            let json_b = GhostJson::new(
+                Utc::now(),
+                vec![
+                    GhostData::new("a_d".to_string(), "renamed".to_string(), None),
+                    GhostData::new("b_d".to_string(), "b_s".to_string(), None),
+                    GhostData::new("c_d".to_string(), "other".to_string(), None),
+                ],
+            );
+
+            let result = diverged_directories(&base, &[json_a, json_b], &GlobalOptions::default());
+            assert_eq!(result, HashSet::from(["c_d".to_string()]));
+        }
+
+        #[test]
+        fn including_removal_against_change() {
+            let base = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("a_d".to_string(), "a_s".to_string(), None)],
+            );
+            let json_a = GhostJson::new(Utc::now(), vec![]);
+            let json_b = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new(
+                    "a_d".to_string(),
+                    "renamed".to_string(),
+                    None,
+                )],
+            );
+
+            let result = diverged_directories(&base, &[json_a, json_b], &GlobalOptions::default());
+            assert_eq!(result, HashSet::from(["a_d".to_string()]));
+        }
+    }
+
+    mod merge_body {
+        use chrono::Duration;
+
+        use crate::ast::{GhostData, Tombstone};
+
+        use super::*;
+
         #[test]
         fn checking_value_when_all_unique() {
             let json_a = GhostJson::new(
@@ -123,7 +796,7 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
             let json_b = GhostJson::new(
@@ -131,7 +804,7 @@ mod tests {
                 vec![GhostData::new(
                     "b_d".to_string(),
                     "b_s".to_string(),
-                    "b_k".to_string(),
+                    Some("b_k".to_string()),
                 )],
             );
             let json_c = GhostJson::new(
@@ -139,18 +812,30 @@ mod tests {
                 vec![GhostData::new(
                     "c_d".to_string(),
                     "c_s".to_string(),
-                    "c_k".to_string(),
+                    Some("c_k".to_string()),
                 )],
             );
             let jsons = vec![json_a, json_b, json_c];
 
-            let result = merge_body(&jsons);
+            let result = merge_body(&jsons, &GlobalOptions::default());
             assert_eq!(
                 result.ghost_list(),
                 &vec![
-                    GhostData::new("a_d".to_string(), "a_s".to_string(), "a_k".to_string()),
-                    GhostData::new("b_d".to_string(), "b_s".to_string(), "b_k".to_string()),
-                    GhostData::new("c_d".to_string(), "c_s".to_string(), "c_k".to_string())
+                    GhostData::new(
+                        "a_d".to_string(),
+                        "a_s".to_string(),
+                        Some("a_k".to_string())
+                    ),
+                    GhostData::new(
+                        "b_d".to_string(),
+                        "b_s".to_string(),
+                        Some("b_k".to_string())
+                    ),
+                    GhostData::new(
+                        "c_d".to_string(),
+                        "c_s".to_string(),
+                        Some("c_k".to_string())
+                    )
                 ]
             );
         }
@@ -162,7 +847,7 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
             let json_b = GhostJson::new(
@@ -170,7 +855,7 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
             let json_c = GhostJson::new(
@@ -178,20 +863,81 @@ mod tests {
                 vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string(),
+                    Some("a_k".to_string()),
                 )],
             );
             let jsons = vec![json_a, json_b, json_c];
 
-            let result = merge_body(&jsons);
+            let result = merge_body(&jsons, &GlobalOptions::default());
             assert_eq!(
                 result.ghost_list(),
                 &vec![GhostData::new(
                     "a_d".to_string(),
                     "a_s".to_string(),
-                    "a_k".to_string()
+                    Some("a_k".to_string())
                 ),]
             );
         }
+
+        #[test]
+        fn keeping_unknown_fields() {
+            let json_a: GhostJson = serde_json::from_str(
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"a_d","sakuraName":"a_s","keroName":"a_k"}],"source":"a"}"#,
+            )
+            .unwrap();
+            let json_b: GhostJson = serde_json::from_str(
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"a_d","sakuraName":"a_s","keroName":"a_k","author":"b"}],"source":"b","note":"b"}"#,
+            )
+            .unwrap();
+            let jsons = vec![json_a, json_b];
+
+            let result = merge_body(&jsons, &GlobalOptions::default());
+            assert_eq!(result.ghost_list().len(), 1);
+            assert_eq!(
+                result.ghost_list()[0].extra().get("author"),
+                Some(&serde_json::Value::String("b".to_string()))
+            );
+            assert_eq!(
+                result.extra().get("source"),
+                Some(&serde_json::Value::String("a".to_string()))
+            );
+            assert_eq!(
+                result.extra().get("note"),
+                Some(&serde_json::Value::String("b".to_string()))
+            );
+        }
+
+        #[test]
+        fn not_resurrecting_when_buried() {
+            let erased = GhostData::new("a_d".to_string(), "a_s".to_string(), None);
+            let erased_at = Utc::now();
+
+            let json_a = GhostJson::new(erased_at - Duration::days(1), vec![erased.clone()]);
+            let json_b = GhostJson::new(erased_at, vec![]).with_tombstones(vec![Tombstone::new(
+                &erased,
+                erased_at,
+                Some("bogus".to_string()),
+            )]);
+            let jsons = vec![json_a, json_b];
+
+            let result = merge_body(&jsons, &GlobalOptions::default());
+            assert!(result.ghost_list().is_empty());
+            assert_eq!(result.tombstones(), jsons[1].tombstones());
+        }
+
+        #[test]
+        fn resurrecting_when_newer_than_tombstone() {
+            let erased = GhostData::new("a_d".to_string(), "a_s".to_string(), None);
+            let erased_at = Utc::now() - Duration::days(1);
+
+            let json_a = GhostJson::new(Utc::now(), vec![erased.clone()]);
+            let json_b = GhostJson::new(erased_at, vec![])
+                .with_tombstones(vec![Tombstone::new(&erased, erased_at, None)]);
+            let jsons = vec![json_a, json_b];
+
+            let result = merge_body(&jsons, &GlobalOptions::default());
+            assert_eq!(result.ghost_list(), &vec![erased]);
+            assert!(result.tombstones().is_empty());
+        }
     }
 }
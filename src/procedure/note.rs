@@ -0,0 +1,106 @@
+use crate::{
+    ast::GhostJson,
+    config::NoteArgs,
+    io::{load_json, write_json},
+    procedure::is_selected,
+};
+
+pub(super) fn note(args: &NoteArgs) -> Result<(), std::io::Error> {
+    let json = load_json(args.target())?;
+
+    let ghost_json = note_body(args, &json)?;
+
+    write_json(args.target(), &ghost_json, args.write_options())
+}
+
+fn note_body(args: &NoteArgs, json: &GhostJson) -> Result<GhostJson, std::io::Error> {
+    let mut ghost_list = json.ghost_list().clone();
+    let mut count = 0;
+
+    for v in ghost_list.iter_mut() {
+        if !is_selected(v, args.directory(), args.sakura_name(), args.kero_name())? {
+            continue;
+        }
+
+        if args.clear() {
+            v.set_note(None);
+        } else {
+            v.set_note(args.set().cloned());
+        }
+        count += 1;
+    }
+
+    eprintln!("noted: {}", count);
+
+    Ok(json.updated(ghost_list))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::config::WriteOptions;
+
+    use super::*;
+
+    mod note {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.json");
+            let original_contents = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":""},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book"}]}"#;
+            let original_json = serde_json::from_str(original_contents).unwrap();
+            write_json(&out_path, &original_json, &WriteOptions::default()).unwrap();
+
+            let args = NoteArgs::new(
+                out_path.clone(),
+                Some("FoxTheory".to_string()),
+                None,
+                None,
+                Some("known-harmless collision".to_string()),
+                false,
+            );
+
+            note(&args).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert_eq!(result.ghost_list()[0].note(), None);
+            assert_eq!(
+                result.ghost_list()[1].note(),
+                Some(&"known-harmless collision".to_string())
+            );
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod note_body {
+        use std::path::PathBuf;
+
+        use crate::ast::GhostData;
+
+        use super::*;
+
+        #[test]
+        fn clearing_note() {
+            let mut v = GhostData::new("d".to_string(), "s".to_string(), None);
+            v.set_note(Some("note".to_string()));
+            let json = GhostJson::new(Utc::now(), vec![v]);
+
+            let args = NoteArgs::new(
+                PathBuf::new(),
+                Some("d".to_string()),
+                None,
+                None,
+                None,
+                true,
+            );
+            let result = note_body(&args, &json).unwrap();
+            assert_eq!(result.ghost_list()[0].note(), None);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::{ast::json_schema, config::SchemaArgs, io::write_text};
+
+pub(super) fn schema(args: &SchemaArgs) -> Result<(), std::io::Error> {
+    let contents = serde_json::to_string_pretty(&json_schema())?;
+
+    write_text(args.output(), &contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod schema {
+        use serde_json::Value;
+        use tempfile::tempdir;
+
+        use crate::io::load_text;
+
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.schema.json");
+
+            let args = SchemaArgs::new(out_path.clone());
+
+            schema(&args).unwrap();
+
+            let result: Value = serde_json::from_str(&load_text(&out_path).unwrap()).unwrap();
+            assert_eq!(result, json_schema());
+            assert_eq!(
+                result["required"],
+                serde_json::json!(["update", "ghostList"])
+            );
+
+            out_dir.close().unwrap();
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    ast::GhostJson,
+    config::TagArgs,
+    io::{load_json, write_json},
+    procedure::is_selected,
+};
+
+pub(super) fn tag(args: &TagArgs) -> Result<(), std::io::Error> {
+    let json = load_json(args.target())?;
+
+    let ghost_json = tag_body(args, &json)?;
+
+    write_json(args.target(), &ghost_json, args.write_options())
+}
+
+fn tag_body(args: &TagArgs, json: &GhostJson) -> Result<GhostJson, std::io::Error> {
+    let mut ghost_list = json.ghost_list().clone();
+    let mut count = 0;
+
+    for v in ghost_list.iter_mut() {
+        if !is_selected(v, args.directory(), args.sakura_name(), args.kero_name())? {
+            continue;
+        }
+
+        if args.clear() {
+            v.clear_tags();
+        }
+        for t in args.remove().iter() {
+            v.remove_tag(t);
+        }
+        for t in args.add().iter() {
+            v.add_tag(t);
+        }
+        count += 1;
+    }
+
+    eprintln!("tagged: {}", count);
+
+    Ok(json.updated(ghost_list))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::config::WriteOptions;
+
+    use super::*;
+
+    mod tag {
+        use tempfile::tempdir;
+
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let out_dir = tempdir().unwrap();
+            let out_path = out_dir.path().join("test.json");
+            let original_contents = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":""},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book","tags":["test"]}]}"#;
+            let original_json = serde_json::from_str(original_contents).unwrap();
+            write_json(&out_path, &original_json, &WriteOptions::default()).unwrap();
+
+            let args = TagArgs::new(
+                out_path.clone(),
+                Some("FoxTheory".to_string()),
+                None,
+                None,
+                vec!["retired".to_string()],
+                vec!["test".to_string()],
+                false,
+            );
+
+            tag(&args).unwrap();
+
+            let result = load_json(&out_path).unwrap();
+            assert!(result.ghost_list()[0].tags().is_empty());
+            assert_eq!(result.ghost_list()[1].tags(), &vec!["retired".to_string()]);
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod tag_body {
+        use std::path::PathBuf;
+
+        use crate::ast::GhostData;
+
+        use super::*;
+
+        #[test]
+        fn clearing_before_adding() {
+            let mut v = GhostData::new("d".to_string(), "s".to_string(), None);
+            v.add_tag("test");
+            v.add_tag("private");
+            let json = GhostJson::new(Utc::now(), vec![v]);
+
+            let args = TagArgs::new(
+                PathBuf::new(),
+                None,
+                Some("s".to_string()),
+                None,
+                vec!["retired".to_string(), "retired".to_string()],
+                vec![],
+                true,
+            );
+            let result = tag_body(&args, &json).unwrap();
+            assert_eq!(result.ghost_list()[0].tags(), &vec!["retired".to_string()]);
+        }
+
+        #[test]
+        fn failed_when_nothing_selected() {
+            let json = GhostJson::new(
+                Utc::now(),
+                vec![GhostData::new("d".to_string(), "s".to_string(), None)],
+            );
+            let args = TagArgs::new(
+                PathBuf::new(),
+                None,
+                None,
+                None,
+                vec!["retired".to_string()],
+                vec![],
+                false,
+            );
+            assert!(tag_body(&args, &json).is_err());
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    config::{ListFormat, ValidateArgs},
+    io::{list_format_of, load_text, may_be_ndjson},
+    validation::{validate_ndjson_str, validate_str},
+};
+
+pub(super) fn validate(args: &ValidateArgs) -> Result<(), std::io::Error> {
+    let contents = load_text(args.target())?;
+
+    let problems = match list_format_of(args.target()) {
+        Some(ListFormat::Json) => validate_str(&contents),
+        Some(ListFormat::Ndjson) => validate_ndjson_str(&contents),
+        None => {
+            let problems = validate_str(&contents);
+            if problems.is_empty() || !may_be_ndjson(&contents) {
+                problems
+            } else {
+                let ndjson_problems = validate_ndjson_str(&contents);
+                if ndjson_problems.is_empty() {
+                    ndjson_problems
+                } else {
+                    problems
+                }
+            }
+        }
+    };
+
+    for problem in problems.iter() {
+        println!("{}:{}", args.target().display(), problem);
+    }
+
+    if problems.is_empty() {
+        println!("{}: ok", args.target().display());
+        Ok(())
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} problem(s) found", problems.len()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod validate {
+        use std::path::PathBuf;
+
+        use tempfile::tempdir;
+
+        use super::*;
+
+        #[test]
+        fn success_when_valid_file() {
+            let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("test_target/json/valid/Konuka.json");
+            let args = ValidateArgs::new(path);
+            assert!(validate(&args).is_ok());
+        }
+
+        #[test]
+        fn failed_when_invalid_file() {
+            let base_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/json/invalid/");
+
+            let args = ValidateArgs::new(base_path.join("no-update.json"));
+            assert!(validate(&args).is_err());
+
+            let args = ValidateArgs::new(base_path.join("no-ghostList.json"));
+            assert!(validate(&args).is_err());
+        }
+
+        #[test]
+        fn checking_ndjson_file() {
+            let dir = tempdir().unwrap();
+
+            let path = dir.path().join("test.ndjson");
+            std::fs::write(
+                &path,
+                "{\"update\":\"2023-12-18T07:18:31.808Z\"}\n\
+                 {\"directory\":\"a\",\"sakuraName\":\"s\",\"keroName\":\"k\"}\n",
+            )
+            .unwrap();
+            assert!(validate(&ValidateArgs::new(path)).is_ok());
+
+            let path = dir.path().join("test.jsonl");
+            std::fs::write(
+                &path,
+                "{\"directory\":\"a\",\"sakuraName\":\"\",\"keroName\":\"k\"}\n",
+            )
+            .unwrap();
+            assert!(validate(&ValidateArgs::new(path)).is_err());
+
+            dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_single_object_without_ghost_list() {
+            let dir = tempdir().unwrap();
+
+            let path = dir.path().join("test");
+            std::fs::write(&path, "{\"update\":\"2023-12-18T07:18:31.808Z\"}\n").unwrap();
+            assert!(validate(&ValidateArgs::new(path)).is_err());
+
+            dir.close().unwrap();
+        }
+    }
+}
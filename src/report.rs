@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    ast::{GhostData, GhostJson},
+    config::NameField,
+};
+
+const HEADER: [&str; 5] = ["directory", "sakuraName", "keroName", "tags", "note"];
+
+const STYLE: &str = r#"body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #999; padding: 0.25em 0.5em; text-align: left; }
+th { background: #eee; cursor: pointer; user-select: none; }
+th[data-order="asc"]::after { content: " \25B2"; }
+th[data-order="desc"]::after { content: " \25BC"; }"#;
+
+const SCRIPT: &str = r#"document.querySelectorAll("table.sortable th").forEach(function (th) {
+  th.addEventListener("click", function () {
+    var tbody = th.closest("table").tBodies[0];
+    var index = th.cellIndex;
+    var ascending = th.dataset.order !== "asc";
+    th.parentNode.querySelectorAll("th").forEach(function (v) { delete v.dataset.order; });
+    th.dataset.order = ascending ? "asc" : "desc";
+    var rows = Array.prototype.slice.call(tbody.rows);
+    rows.sort(function (a, b) {
+      var r = a.cells[index].textContent.localeCompare(b.cells[index].textContent, "ja");
+      return ascending ? r : -r;
+    });
+    rows.forEach(function (row) { tbody.appendChild(row); });
+  });
+});"#;
+
+/// entries grouped by the first character of sakuraName, or all in one unnamed group.
+fn groups_of(json: &GhostJson, group: bool) -> Vec<(Option<String>, Vec<&GhostData>)> {
+    if !group {
+        return vec![(None, json.ghost_list().iter().collect())];
+    }
+
+    let mut groups: BTreeMap<String, Vec<&GhostData>> = BTreeMap::new();
+    for v in json.ghost_list().iter() {
+        let key = v
+            .sakura_name()
+            .chars()
+            .next()
+            .map(|c| c.to_string())
+            .unwrap_or_default();
+        groups.entry(key).or_default().push(v);
+    }
+
+    groups.into_iter().map(|(k, v)| (Some(k), v)).collect()
+}
+
+fn cells_of(v: &GhostData) -> [String; 5] {
+    [
+        v.directory().clone(),
+        v.sakura_name().clone(),
+        v.kero_name().cloned().unwrap_or_default(),
+        v.tags().join(", "),
+        v.note().cloned().unwrap_or_default(),
+    ]
+}
+
+/// renders ghost data as a self-contained html page with sortable tables.
+/// `charset` is declared as the encoding the page is written in.
+pub(crate) fn write_html(json: &GhostJson, group: bool, charset: &str) -> String {
+    let mut contents = String::new();
+    contents.push_str(&format!(
+        "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"{}\">\n",
+        charset
+    ));
+    contents.push_str("<title>ghost list</title>\n");
+    contents.push_str(&format!("<style>\n{}\n</style>\n", STYLE));
+    contents.push_str("</head>\n<body>\n<h1>ghost list</h1>\n");
+    contents.push_str(&format!(
+        "<p>update: {}<br>count: {}</p>\n",
+        escape_html(json.update()),
+        json.ghost_list().len()
+    ));
+
+    for (name, entries) in groups_of(json, group) {
+        if let Some(name) = name {
+            contents.push_str(&format!(
+                "<h2>{} ({})</h2>\n",
+                escape_html(&name),
+                entries.len()
+            ));
+        }
+        contents.push_str("<table class=\"sortable\">\n<thead><tr>");
+        for h in HEADER {
+            contents.push_str(&format!("<th>{}</th>", h));
+        }
+        contents.push_str("</tr></thead>\n<tbody>\n");
+        for v in entries {
+            contents.push_str("<tr>");
+            for cell in cells_of(v) {
+                contents.push_str(&format!("<td>{}</td>", escape_html(&cell)));
+            }
+            contents.push_str("</tr>\n");
+        }
+        contents.push_str("</tbody>\n</table>\n");
+    }
+
+    contents.push_str(&format!("<script>\n{}\n</script>\n", SCRIPT));
+    contents.push_str("</body>\n</html>\n");
+
+    contents
+}
+
+/// renders ghost data as markdown tables.
+pub(crate) fn write_markdown(json: &GhostJson, group: bool) -> String {
+    let mut contents = String::new();
+    contents.push_str("# ghost list\n\n");
+    contents.push_str(&format!("- update: {}\n", json.update()));
+    contents.push_str(&format!("- count: {}\n", json.ghost_list().len()));
+
+    for (name, entries) in groups_of(json, group) {
+        contents.push('\n');
+        if let Some(name) = name {
+            contents.push_str(&format!(
+                "## {} ({})\n\n",
+                escape_markdown(&name),
+                entries.len()
+            ));
+        }
+        contents.push_str(&format!("| {} |\n", HEADER.join(" | ")));
+        contents.push_str(&format!("|{}\n", " --- |".repeat(HEADER.len())));
+        for v in entries {
+            let cells: Vec<String> = cells_of(v).iter().map(|v| escape_markdown(v)).collect();
+            contents.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+    }
+
+    contents
+}
+
+/// names of ghost data in list order. blank names are left out.
+pub(crate) fn name_list(json: &GhostJson, field: NameField) -> Vec<&String> {
+    let mut names = Vec::new();
+    for v in json.ghost_list().iter() {
+        if matches!(field, NameField::Directory | NameField::All) {
+            names.push(v.directory());
+        }
+        if matches!(field, NameField::Sakura | NameField::All) {
+            names.push(v.sakura_name());
+        }
+        if matches!(field, NameField::Kero | NameField::All) {
+            names.extend(v.kero_name());
+        }
+    }
+    names.retain(|v| !v.is_empty());
+    names
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// escapes characters which break a table cell or are taken as markup.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '|' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("<br>"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> GhostJson {
+        let mut tagged = GhostData::new(
+            "FoxTheory".to_string(),
+            "リサ".to_string(),
+            Some("book".to_string()),
+        );
+        tagged.add_tag("retired");
+        tagged.set_note(Some("<b>a|b</b>".to_string()));
+
+        serde_json::from_str::<GhostJson>(r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[]}"#)
+            .unwrap()
+            .with_ghost_list(vec![
+                GhostData::new("100th_year".to_string(), "霊".to_string(), None),
+                tagged,
+                GhostData::new(
+                    "lisa2".to_string(),
+                    "リサ".to_string(),
+                    Some("".to_string()),
+                ),
+            ])
+    }
+
+    mod write_html {
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let result = write_html(&sample(), false, "UTF-8");
+            assert!(result.starts_with("<!DOCTYPE html>\n"));
+            assert!(result.contains("<meta charset=\"UTF-8\">"));
+            assert!(result.contains("<p>update: 2023-12-18T07:18:31.808Z<br>count: 3</p>"));
+            assert!(result.contains(
+                "<tr><td>FoxTheory</td><td>リサ</td><td>book</td><td>retired</td><td>&lt;b&gt;a|b&lt;/b&gt;</td></tr>"
+            ));
+            assert_eq!(result.matches("<table").count(), 1);
+            assert!(!result.contains("<h2>"));
+            assert!(!result.contains("src="));
+        }
+
+        #[test]
+        fn grouping_by_first_character() {
+            let result = write_html(&sample(), true, "UTF-8");
+            assert_eq!(result.matches("<table").count(), 2);
+            assert!(result.contains("<h2>リ (2)</h2>"));
+            assert!(result.contains("<h2>霊 (1)</h2>"));
+        }
+    }
+
+    mod name_list {
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let json = sample();
+            assert_eq!(
+                name_list(&json, NameField::Directory),
+                vec!["100th_year", "FoxTheory", "lisa2"]
+            );
+            assert_eq!(
+                name_list(&json, NameField::Sakura),
+                vec!["霊", "リサ", "リサ"]
+            );
+            assert_eq!(name_list(&json, NameField::Kero), vec!["book"]);
+            assert_eq!(
+                name_list(&json, NameField::All),
+                vec![
+                    "100th_year",
+                    "霊",
+                    "FoxTheory",
+                    "リサ",
+                    "book",
+                    "lisa2",
+                    "リサ"
+                ]
+            );
+        }
+    }
+
+    mod write_markdown {
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let result = write_markdown(&sample(), false);
+            assert_eq!(
+                result,
+                "# ghost list\n\
+                 \n\
+                 - update: 2023-12-18T07:18:31.808Z\n\
+                 - count: 3\n\
+                 \n\
+                 | directory | sakuraName | keroName | tags | note |\n\
+                 | --- | --- | --- | --- | --- |\n\
+                 | 100th\\_year | 霊 |  |  |  |\n\
+                 | FoxTheory | リサ | book | retired | \\<b\\>a\\|b\\</b\\> |\n\
+                 | lisa2 | リサ |  |  |  |\n"
+            );
+        }
+
+        #[test]
+        fn grouping_by_first_character() {
+            let result = write_markdown(&sample(), true);
+            let lisa = result.find("## リ (2)").unwrap();
+            let rei = result.find("## 霊 (1)").unwrap();
+            assert!(lisa < rei);
+        }
+    }
+}
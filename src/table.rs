@@ -0,0 +1,279 @@
+use std::collections::BTreeSet;
+
+use serde_json::{Map, Value};
+
+use crate::ast::{GhostData, GhostJson};
+
+const DIRECTORY: &str = "directory";
+const SAKURA_NAME: &str = "sakuraName";
+const KERO_NAME: &str = "keroName";
+const NO_KERO: &str = "noKero";
+const BLANK_KERO: &str = "blankKero";
+const TAGS: &str = "tags";
+const NOTE: &str = "note";
+
+/// separator of tags in a cell.
+const TAG_SEPARATOR: char = ';';
+
+/// writes ghost data as a table with a header row, separated by `delimiter`.
+pub(crate) fn write_table(json: &GhostJson, delimiter: u8) -> Result<String, std::io::Error> {
+    let ghost_list = json.ghost_list();
+
+    let has_no_kero = ghost_list.iter().any(|v| v.kero_name().is_none());
+    let has_blank_kero = ghost_list
+        .iter()
+        .any(|v| v.kero_name().is_some_and(|k| k.is_empty()));
+    let has_tags = ghost_list.iter().any(|v| !v.tags().is_empty());
+    let has_note = ghost_list.iter().any(|v| v.note().is_some());
+    let extra_keys: BTreeSet<&String> = ghost_list.iter().flat_map(|v| v.extra().keys()).collect();
+
+    let mut header = vec![DIRECTORY, SAKURA_NAME, KERO_NAME];
+    if has_no_kero {
+        header.push(NO_KERO);
+    }
+    if has_blank_kero {
+        header.push(BLANK_KERO);
+    }
+    if has_tags {
+        header.push(TAGS);
+    }
+    if has_note {
+        header.push(NOTE);
+    }
+    header.extend(extra_keys.iter().map(|v| v.as_str()));
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(Vec::new());
+    writer.write_record(&header)?;
+
+    for v in ghost_list.iter() {
+        let mut record = vec![
+            v.directory().clone(),
+            v.sakura_name().clone(),
+            v.kero_name().cloned().unwrap_or_default(),
+        ];
+        if has_no_kero {
+            let no_kero = if v.kero_name().is_none() { "true" } else { "" };
+            record.push(no_kero.to_string());
+        }
+        if has_blank_kero {
+            let blank_kero = if v.kero_name().is_some_and(|k| k.is_empty()) {
+                "true"
+            } else {
+                ""
+            };
+            record.push(blank_kero.to_string());
+        }
+        if has_tags {
+            record.push(v.tags().join(&TAG_SEPARATOR.to_string()));
+        }
+        if has_note {
+            record.push(v.note().cloned().unwrap_or_default());
+        }
+        for k in extra_keys.iter() {
+            let cell = match v.extra().get(*k) {
+                None => String::new(),
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+            };
+            record.push(cell);
+        }
+        writer.write_record(&record)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    String::from_utf8(bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// reads ghost data from a table with a header row, separated by `delimiter`.
+/// an empty `keroName` means no kero, as in json, unless `blankKero` is set.
+/// columns other than known ones are kept as unknown fields of strings.
+pub(crate) fn read_table(contents: &str, delimiter: u8) -> Result<Vec<GhostData>, std::io::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(contents.as_bytes());
+
+    let header = reader.headers()?.clone();
+    let column = |name: &str| header.iter().position(|v| v == name);
+
+    let directory_column = column(DIRECTORY).ok_or(missing_column(DIRECTORY))?;
+    let sakura_name_column = column(SAKURA_NAME).ok_or(missing_column(SAKURA_NAME))?;
+    let kero_name_column = column(KERO_NAME);
+    let no_kero_column = column(NO_KERO);
+    let blank_kero_column = column(BLANK_KERO);
+    let tags_column = column(TAGS);
+    let note_column = column(NOTE);
+    let known_columns = [
+        Some(directory_column),
+        Some(sakura_name_column),
+        kero_name_column,
+        no_kero_column,
+        blank_kero_column,
+        tags_column,
+        note_column,
+    ];
+
+    let mut ghost_list = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let cell = |i: Option<usize>| i.and_then(|i| record.get(i)).unwrap_or("");
+
+        let flag = |i: Option<usize>| matches!(cell(i).trim(), "true" | "TRUE" | "1");
+        let kero_name = match cell(kero_name_column) {
+            _ if flag(no_kero_column) => None,
+            "" if !flag(blank_kero_column) => None,
+            v => Some(v.to_string()),
+        };
+
+        let mut v = GhostData::new(
+            cell(Some(directory_column)).to_string(),
+            cell(Some(sakura_name_column)).to_string(),
+            kero_name,
+        );
+        for tag in cell(tags_column).split(TAG_SEPARATOR) {
+            let tag = tag.trim();
+            if !tag.is_empty() {
+                v.add_tag(tag);
+            }
+        }
+        let note = cell(note_column);
+        if !note.is_empty() {
+            v.set_note(Some(note.to_string()));
+        }
+
+        let mut extra = Map::new();
+        for (i, name) in header.iter().enumerate() {
+            if known_columns.contains(&Some(i)) {
+                continue;
+            }
+            let value = cell(Some(i));
+            if !value.is_empty() {
+                extra.insert(name.to_string(), Value::String(value.to_string()));
+            }
+        }
+
+        ghost_list.push(v.with_extra(extra));
+    }
+
+    Ok(ghost_list)
+}
+
+fn missing_column(name: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("missing column `{}`", name),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod write_table {
+        use chrono::Utc;
+
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let mut extra = Map::new();
+            extra.insert("author".to_string(), Value::String("tukinami".to_string()));
+            extra.insert("rank".to_string(), Value::from(3));
+            let mut tagged = GhostData::new(
+                "FoxTheory".to_string(),
+                "リサ, \"the fox\"".to_string(),
+                Some("book".to_string()),
+            )
+            .with_extra(extra);
+            tagged.add_tag("retired");
+            tagged.add_tag("test");
+
+            let json = GhostJson::new(
+                Utc::now(),
+                vec![
+                    GhostData::new("100th_year".to_string(), "霊".to_string(), None),
+                    tagged,
+                    GhostData::new(
+                        "tcidelam".to_string(),
+                        "シデラム".to_string(),
+                        Some("".to_string()),
+                    ),
+                ],
+            );
+
+            let result = write_table(&json, b',').unwrap();
+            assert_eq!(
+                result,
+                "directory,sakuraName,keroName,noKero,blankKero,tags,author,rank\n\
+                 100th_year,霊,,true,,,,\n\
+                 FoxTheory,\"リサ, \"\"the fox\"\"\",book,,,retired;test,tukinami,3\n\
+                 tcidelam,シデラム,,,true,,,\n"
+            );
+
+            let result = write_table(&json, b'\t').unwrap();
+            assert!(
+                result.starts_with("directory\tsakuraName\tkeroName\tnoKero\tblankKero\ttags\t")
+            );
+        }
+    }
+
+    mod read_table {
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let case = "sakuraName,directory,keroName,noKero,blankKero,tags,note,author\n\
+                        霊,100th_year,,true,,,,\n\
+                        \"リサ, \"\"the fox\"\"\",FoxTheory,book,,,retired; test,known,tukinami\n\
+                        シデラム,tcidelam,,,true,,,\n\
+                        きつね,tanumki,,,,,,\n";
+            let result = read_table(case, b',').unwrap();
+
+            let mut extra = Map::new();
+            extra.insert("author".to_string(), Value::String("tukinami".to_string()));
+            let mut tagged = GhostData::new(
+                "FoxTheory".to_string(),
+                "リサ, \"the fox\"".to_string(),
+                Some("book".to_string()),
+            )
+            .with_extra(extra);
+            tagged.add_tag("retired");
+            tagged.add_tag("test");
+            tagged.set_note(Some("known".to_string()));
+
+            assert_eq!(
+                result,
+                vec![
+                    GhostData::new("100th_year".to_string(), "霊".to_string(), None),
+                    tagged,
+                    GhostData::new(
+                        "tcidelam".to_string(),
+                        "シデラム".to_string(),
+                        Some("".to_string())
+                    ),
+                    GhostData::new("tanumki".to_string(), "きつね".to_string(), None),
+                ]
+            );
+        }
+
+        #[test]
+        fn keeping_other_columns_as_strings() {
+            let case = "directory,sakuraName,rank\na,a,3\n";
+            let result = read_table(case, b',').unwrap();
+            assert_eq!(
+                result[0].extra().get("rank"),
+                Some(&Value::String("3".to_string()))
+            );
+        }
+
+        #[test]
+        fn failed_when_missing_column() {
+            let case = "directory,keroName\na,b\n";
+            assert!(read_table(case, b',').is_err());
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::ast::GhostJson;
+
+const DECLARATIONS: &str = r#"export interface GhostData {
+  directory: string;
+  sakuraName: string;
+  keroName: string;
+  noKero?: boolean;
+  blankKero?: boolean;
+  tags?: string[];
+  note?: string;
+  [key: string]: unknown;
+}
+
+export interface Tombstone {
+  ghost: GhostData;
+  erasedAt: string;
+  reason?: string;
+}
+
+export interface GhostJson {
+  update: string;
+  ghostList: GhostData[];
+  tombstones?: Tombstone[];
+  [key: string]: unknown;
+}
+"#;
+
+/// renders ghost data as a typescript module exporting `ghostJson` with its types.
+pub(crate) fn write_typescript(json: &GhostJson) -> Result<String, serde_json::Error> {
+    let literal = to_literal(&serde_json::to_string_pretty(json)?);
+
+    Ok(format!(
+        "// generated by kaburimake-checker-utils-rs. do not edit.\n\n\
+         {}\n\
+         export const ghostJson: GhostJson = {};\n\n\
+         export default ghostJson;\n",
+        DECLARATIONS, literal
+    ))
+}
+
+/// json is a valid typescript literal except for line and paragraph separators in strings,
+/// which older parsers take as line breaks.
+fn to_literal(json: &str) -> String {
+    json.replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod write_typescript {
+        use super::*;
+
+        #[test]
+        fn checking_value() {
+            let json: GhostJson = serde_json::from_str(
+                r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"a","sakuraName":"\"quoted\"\\back\u2028","keroName":"","noKero":true}]}"#,
+            )
+            .unwrap();
+
+            let result = write_typescript(&json).unwrap();
+            assert!(result.contains("export interface GhostData {\n"));
+            assert!(result.contains("export interface GhostJson {\n"));
+            assert!(result.contains(
+                "export const ghostJson: GhostJson = {\n  \"update\": \"2023-12-18T07:18:31.808Z\",\n"
+            ));
+            assert!(result.contains(r#""sakuraName": "\"quoted\"\\back\u2028","#));
+            assert!(!result.contains('\u{2028}'));
+            assert!(result.ends_with("};\n\nexport default ghostJson;\n"));
+        }
+    }
+}
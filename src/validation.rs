@@ -0,0 +1,913 @@
+use std::{borrow::Cow, collections::HashMap, fmt, ops::Range};
+
+use chrono::Utc;
+use serde_json::{Map, Value};
+
+use crate::ast::{directory_key, GhostData, GhostJson, Tombstone};
+
+/// a problem found in a ghost data json.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Problem {
+    line: usize,
+    column: usize,
+    path: String,
+    message: String,
+}
+
+impl Problem {
+    fn new(position: (usize, usize), path: &str, message: String) -> Problem {
+        Problem {
+            line: position.0,
+            column: position.1,
+            path: path.to_string(),
+            message,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+
+    #[cfg(test)]
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, self.path, self.message
+        )
+    }
+}
+
+/// checks `contents` as ghost data json, and returns all problems found.
+pub(crate) fn validate_str(contents: &str) -> Vec<Problem> {
+    let value = match serde_json::from_str::<Value>(contents) {
+        Ok(v) => v,
+        Err(e) => {
+            return vec![Problem::new((e.line(), e.column()), "$", e.to_string())];
+        }
+    };
+
+    let locations = Locations::new(contents);
+    let problem = |path: &str, message: String| locations.problem(path, message);
+    let mut problems = Vec::new();
+
+    let root = match value.as_object() {
+        Some(v) => v,
+        None => {
+            problems.push(problem("$", "expected an object".to_string()));
+            return problems;
+        }
+    };
+
+    check_header(root, &problem, &mut problems);
+
+    match root.get("ghostList") {
+        None => problems.push(problem("$", "missing field `ghostList`".to_string())),
+        Some(Value::Array(v)) => check_ghost_list(v, &problem, &mut problems),
+        Some(v) => problems.push(problem("$.ghostList", expected("an array", v))),
+    }
+
+    problems
+}
+
+/// checks `contents` as newline-delimited ghost data, and returns all problems found.
+/// paths are reported as if the lines were a single json.
+pub(crate) fn validate_ndjson_str(contents: &str) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut header = None;
+    let mut entries = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value = match serde_json::from_str::<Value>(line) {
+            Ok(v) => v,
+            Err(e) => {
+                problems.push(Problem::new((i + 1, e.column()), "$", e.to_string()));
+                continue;
+            }
+        };
+        let is_header = value.is_object() && value.get("directory").is_none();
+        if is_header && header.is_some() {
+            problems.push(Problem::new((i + 1, 1), "$", "second header".to_string()));
+        } else if is_header {
+            header = Some((i + 1, Locations::new(line), value));
+        } else {
+            entries.push((i + 1, Locations::new(line), value));
+        }
+    }
+
+    if let Some((line, locations, Value::Object(root))) = &header {
+        let problem = |path: &str, message: String| {
+            let (_, column) = locations.position(path);
+            Problem::new((*line, column), path, message)
+        };
+        check_header(root, &problem, &mut problems);
+    }
+
+    let problem = |path: &str, message: String| {
+        let entry = path
+            .strip_prefix("$.ghostList[")
+            .and_then(|v| v.split_once(']'))
+            .and_then(|(i, rest)| Some((entries.get(i.parse::<usize>().ok()?)?, rest)));
+        match entry {
+            Some(((line, locations, _), rest)) => {
+                let (_, column) = locations.position(&format!("${}", rest));
+                Problem::new((*line, column), path, message)
+            }
+            None => Problem::new((1, 1), path, message),
+        }
+    };
+    let ghost_list: Vec<Value> = entries.iter().map(|(_, _, v)| v.clone()).collect();
+    check_ghost_list(&ghost_list, &problem, &mut problems);
+
+    problems.sort_by_key(|v| (v.line, v.column));
+    problems
+}
+
+/// ghost data recovered from damaged json `contents`, and problems of what was left out.
+/// entries are taken as far as the text is well-formed, and one by one by their types.
+/// `update` is left blank, that is unknown, when it is missing or invalid.
+pub(crate) fn salvage_str(contents: &str) -> (GhostJson, Vec<Problem>) {
+    let locations = Locations::new(contents);
+    let mut problems = Vec::new();
+
+    let (root, well_formed) = match serde_json::from_str::<Value>(contents) {
+        Ok(Value::Object(v)) => (v, true),
+        Ok(_) => {
+            problems.push(locations.problem("$", "expected an object".to_string()));
+            (Map::new(), false)
+        }
+        Err(e) => {
+            problems.push(Problem::new((e.line(), e.column()), "$", e.to_string()));
+            (Map::new(), false)
+        }
+    };
+
+    let update = locations
+        .text("$.update")
+        .and_then(|v| serde_json::from_str::<Value>(v).ok());
+    let update = match update {
+        Some(Value::String(v)) if chrono::DateTime::parse_from_rfc3339(&v).is_ok() => v,
+        Some(Value::String(v)) => {
+            problems.push(locations.problem(
+                "$.update",
+                format!("`{}` is not a RFC 3339 date-time, taken as unknown", v),
+            ));
+            String::new()
+        }
+        Some(v) => {
+            problems.push(locations.problem(
+                "$.update",
+                format!("{}, taken as unknown", expected("a string", &v)),
+            ));
+            String::new()
+        }
+        None => {
+            problems.push(
+                locations.problem("$", "missing field `update`, taken as unknown".to_string()),
+            );
+            String::new()
+        }
+    };
+
+    // the error of a damaged text is already reported above.
+    match root.get("ghostList") {
+        None if well_formed => {
+            problems.push(locations.problem("$", "missing field `ghostList`".to_string()))
+        }
+        Some(v) if !v.is_array() => {
+            problems.push(locations.problem("$.ghostList", expected("an array", v)))
+        }
+        _ => {}
+    }
+    for path in locations.too_deep.iter() {
+        problems.push(locations.problem(
+            path,
+            format!("skipped: nested deeper than {} levels", MAX_DEPTH),
+        ));
+    }
+    let ghost_list: Vec<GhostData> = salvage_array(&locations, "$.ghostList", &mut problems);
+    let tombstones: Vec<Tombstone> = salvage_array(&locations, "$.tombstones", &mut problems);
+
+    let mut extra = root;
+    for key in ["update", "ghostList", "tombstones"] {
+        extra.remove(key);
+    }
+
+    problems.sort_by_key(|v| (v.line, v.column));
+
+    let json = GhostJson::new(Utc::now(), ghost_list)
+        .with_update(update)
+        .with_tombstones(tombstones)
+        .with_extra(extra);
+    (json, problems)
+}
+
+/// well-formed elements of the array at `path`, skipping others with problems.
+fn salvage_array<T>(locations: &Locations, path: &str, problems: &mut Vec<Problem>) -> Vec<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let mut values = Vec::new();
+    for i in 0.. {
+        let path = format!("{}[{}]", path, i);
+        let Some(text) = locations.text(&path) else {
+            break;
+        };
+        match serde_json::from_str::<Value>(text).and_then(serde_json::from_value::<T>) {
+            Ok(v) => values.push(v),
+            Err(e) => problems.push(locations.problem(&path, format!("skipped: {}", e))),
+        }
+    }
+    values
+}
+
+/// ghost data recovered from damaged newline-delimited `contents`, and problems of what was left out.
+/// lines which are not well-formed are skipped.
+pub(crate) fn salvage_ndjson_str(contents: &str) -> (GhostJson, Vec<Problem>) {
+    let mut problems = Vec::new();
+    let mut header: Option<GhostJson> = None;
+    let mut ghost_list = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut value = match serde_json::from_str::<Value>(line) {
+            Ok(v) => v,
+            Err(e) => {
+                problems.push(Problem::new(
+                    (i + 1, e.column()),
+                    "$",
+                    format!("skipped: {}", e),
+                ));
+                continue;
+            }
+        };
+
+        let is_header = value.is_object() && value.get("directory").is_none();
+        let skipped = if !is_header {
+            serde_json::from_value::<GhostData>(value)
+                .map(|v| ghost_list.push(v))
+                .err()
+        } else if header.is_none() {
+            if let Some(v) = value.as_object_mut() {
+                v.entry("ghostList").or_insert(Value::Array(Vec::new()));
+            }
+            serde_json::from_value::<GhostJson>(value)
+                .map(|v| header = Some(v))
+                .err()
+        } else {
+            problems.push(Problem::new(
+                (i + 1, 1),
+                "$",
+                "skipped: second header".to_string(),
+            ));
+            None
+        };
+        if let Some(e) = skipped {
+            problems.push(Problem::new((i + 1, 1), "$", format!("skipped: {}", e)));
+        }
+    }
+
+    let header =
+        header.unwrap_or_else(|| GhostJson::new(Utc::now(), Vec::new()).with_update(String::new()));
+    let ghost_list = header
+        .ghost_list()
+        .iter()
+        .cloned()
+        .chain(ghost_list)
+        .collect();
+    (header.with_ghost_list(ghost_list), problems)
+}
+
+fn check_header(
+    root: &Map<String, Value>,
+    problem: &dyn Fn(&str, String) -> Problem,
+    problems: &mut Vec<Problem>,
+) {
+    match root.get("update") {
+        None => problems.push(problem("$", "missing field `update`".to_string())),
+        Some(Value::String(v)) => {
+            if chrono::DateTime::parse_from_rfc3339(v).is_err() {
+                problems.push(problem(
+                    "$.update",
+                    format!("`{}` is not a RFC 3339 date-time", v),
+                ));
+            }
+        }
+        Some(v) => problems.push(problem("$.update", expected("a string", v))),
+    }
+
+    match root.get("tombstones") {
+        None => {}
+        Some(Value::Array(tombstones)) => {
+            for (i, t) in tombstones.iter().enumerate() {
+                let path = format!("$.tombstones[{}]", i);
+                match t.get("ghost") {
+                    Some(Value::Object(_)) => {}
+                    Some(v) => problems.push(problem(
+                        &format!("{}.ghost", path),
+                        expected("an object", v),
+                    )),
+                    None => problems.push(problem(&path, "missing field `ghost`".to_string())),
+                }
+                match t.get("erasedAt") {
+                    Some(Value::String(_)) => {}
+                    Some(v) => problems.push(problem(
+                        &format!("{}.erasedAt", path),
+                        expected("a string", v),
+                    )),
+                    None => problems.push(problem(&path, "missing field `erasedAt`".to_string())),
+                }
+            }
+        }
+        Some(v) => problems.push(problem("$.tombstones", expected("an array", v))),
+    }
+}
+
+fn check_ghost_list(
+    ghost_list: &[Value],
+    problem: &dyn Fn(&str, String) -> Problem,
+    problems: &mut Vec<Problem>,
+) {
+    let mut seen: HashMap<(Cow<str>, &str, Option<&str>), usize> = HashMap::new();
+    for (i, entry) in ghost_list.iter().enumerate() {
+        let path = format!("$.ghostList[{}]", i);
+        let entry = match entry.as_object() {
+            Some(v) => v,
+            None => {
+                problems.push(problem(&path, expected("an object", entry)));
+                continue;
+            }
+        };
+
+        let mut fields = Vec::new();
+        for key in ["directory", "sakuraName", "keroName"] {
+            match entry.get(key) {
+                None => problems.push(problem(&path, format!("missing field `{}`", key))),
+                Some(Value::String(v)) => fields.push(v.as_str()),
+                Some(v) => problems.push(problem(
+                    &format!("{}.{}", path, key),
+                    expected("a string", v),
+                )),
+            }
+        }
+
+        if let Some(Value::String(v)) = entry.get("directory") {
+            if v.is_empty() {
+                problems.push(problem(
+                    &format!("{}.directory", path),
+                    "`directory` is empty".to_string(),
+                ));
+            }
+        }
+        if let Some(Value::String(v)) = entry.get("sakuraName") {
+            if v.is_empty() {
+                problems.push(problem(
+                    &format!("{}.sakuraName", path),
+                    "`sakuraName` is empty".to_string(),
+                ));
+            }
+        }
+
+        match entry.get("tags") {
+            None => {}
+            Some(Value::Array(tags)) => {
+                for (j, t) in tags.iter().enumerate() {
+                    if !t.is_string() {
+                        problems.push(problem(
+                            &format!("{}.tags[{}]", path, j),
+                            expected("a string", t),
+                        ));
+                    }
+                }
+            }
+            Some(v) => problems.push(problem(&format!("{}.tags", path), expected("an array", v))),
+        }
+        if let Some(v) = entry.get("note").filter(|v| !v.is_string()) {
+            problems.push(problem(&format!("{}.note", path), expected("a string", v)));
+        }
+
+        let mut flag = |key: &str| match entry.get(key) {
+            None => false,
+            Some(Value::Bool(v)) => *v,
+            Some(v) => {
+                problems.push(problem(
+                    &format!("{}.{}", path, key),
+                    expected("a boolean", v),
+                ));
+                false
+            }
+        };
+        let no_kero = flag("noKero");
+        let blank_kero = flag("blankKero");
+
+        if let [directory, sakura_name, kero_name] = fields[..] {
+            // same as loading: a blank `keroName` without `blankKero` is of an older list.
+            let kero_name = Some(kero_name).filter(|v| !no_kero && (!v.is_empty() || blank_kero));
+            let key = (directory_key(directory), sakura_name, kero_name);
+            match seen.get(&key) {
+                Some(first) => problems.push(problem(
+                    &path,
+                    format!("duplicate of $.ghostList[{}]", first),
+                )),
+                None => {
+                    seen.insert(key, i);
+                }
+            }
+        }
+    }
+}
+
+fn expected(what: &str, found: &Value) -> String {
+    let found = match found {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    };
+    format!("expected {}, found {}", what, found)
+}
+
+/// how deep arrays and objects are scanned. deeper values are skipped as a whole.
+const MAX_DEPTH: usize = 128;
+
+/// positions of values in json text, keyed by JSON path such as `$.ghostList[0].directory`.
+pub(crate) struct Locations<'a> {
+    contents: &'a str,
+    spans: HashMap<String, Range<usize>>,
+    /// paths of values skipped for nesting deeper than `MAX_DEPTH`.
+    too_deep: Vec<String>,
+}
+
+impl<'a> Locations<'a> {
+    /// scans `contents` as far as it is well-formed json.
+    pub fn new(contents: &'a str) -> Locations<'a> {
+        let mut scanner = Scanner {
+            bytes: contents.as_bytes(),
+            pos: 0,
+            depth: 0,
+            spans: HashMap::new(),
+            too_deep: Vec::new(),
+        };
+        scanner.value("$".to_string());
+
+        Locations {
+            contents,
+            spans: scanner.spans,
+            too_deep: scanner.too_deep,
+        }
+    }
+
+    /// line and column (both 1-based) where the value at `path` begins.
+    pub fn position(&self, path: &str) -> (usize, usize) {
+        let offset = self.spans.get(path).map(|v| v.start).unwrap_or(0);
+        line_column(self.contents, offset)
+    }
+
+    fn problem(&self, path: &str, message: String) -> Problem {
+        Problem::new(self.position(path), path, message)
+    }
+
+    /// text of the value at `path`, if scanned.
+    fn text(&self, path: &str) -> Option<&'a str> {
+        let span = self.spans.get(path)?;
+        Some(&self.contents[span.clone()])
+    }
+}
+
+pub(crate) fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|v| v + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+    spans: HashMap<String, Range<usize>>,
+    too_deep: Vec<String>,
+}
+
+impl Scanner<'_> {
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self, path: String) -> Option<()> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.bytes.get(self.pos)? {
+            b'{' | b'[' if self.depth >= MAX_DEPTH => {
+                self.nested()?;
+                self.too_deep.push(path.clone());
+            }
+            b'{' => {
+                self.depth += 1;
+                self.object(&path)?;
+                self.depth -= 1;
+            }
+            b'[' => {
+                self.depth += 1;
+                self.array(&path)?;
+                self.depth -= 1;
+            }
+            b'"' => self.string()?,
+            _ => self.scalar()?,
+        }
+        self.spans.insert(path, start..self.pos);
+        Some(())
+    }
+
+    /// passes over an array or object without looking into its values.
+    fn nested(&mut self) -> Option<()> {
+        let mut depth = 0usize;
+        loop {
+            match self.bytes.get(self.pos)? {
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => depth -= 1,
+                b'"' => {
+                    self.string()?;
+                    continue;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+            if depth == 0 {
+                return Some(());
+            }
+        }
+    }
+
+    fn object(&mut self, path: &str) -> Option<()> {
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Some(());
+        }
+        loop {
+            self.skip_whitespace();
+            let key_start = self.pos;
+            self.string()?;
+            let key = std::str::from_utf8(&self.bytes[key_start..self.pos]).ok()?;
+            let key: String = serde_json::from_str(key).ok()?;
+            self.skip_whitespace();
+            if self.bytes.get(self.pos) != Some(&b':') {
+                return None;
+            }
+            self.pos += 1;
+            self.value(format!("{}.{}", path, key))?;
+            self.skip_whitespace();
+            match self.bytes.get(self.pos)? {
+                b',' => self.pos += 1,
+                b'}' => {
+                    self.pos += 1;
+                    return Some(());
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn array(&mut self, path: &str) -> Option<()> {
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Some(());
+        }
+        let mut index = 0;
+        loop {
+            self.value(format!("{}[{}]", path, index))?;
+            index += 1;
+            self.skip_whitespace();
+            match self.bytes.get(self.pos)? {
+                b',' => self.pos += 1,
+                b']' => {
+                    self.pos += 1;
+                    return Some(());
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<()> {
+        if self.bytes.get(self.pos) != Some(&b'"') {
+            return None;
+        }
+        self.pos += 1;
+        loop {
+            match self.bytes.get(self.pos)? {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    self.pos += 1;
+                    return Some(());
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    fn scalar(&mut self) -> Option<()> {
+        let start = self.pos;
+        while let Some(b) = self.bytes.get(self.pos) {
+            if matches!(b, b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r') {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            None
+        } else {
+            Some(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod validate_str {
+        use super::*;
+
+        #[test]
+        fn nothing_when_valid_str() {
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"100th_year","sakuraName":"霊","keroName":""},{"directory":"FoxTheory","sakuraName":"リサ","keroName":"book"}]}"#;
+            assert!(validate_str(case).is_empty());
+        }
+
+        #[test]
+        fn syntax_error_with_position() {
+            let case = "{\"update\":\"2023-12-18T07:18:31.808Z\",\n\"ghostList\":[}";
+            let result = validate_str(case);
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].line, 2);
+        }
+
+        #[test]
+        fn missing_fields() {
+            let case = r#"{"ghostList":[]}"#;
+            let result = validate_str(case);
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].message(), "missing field `update`");
+
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z"}"#;
+            let result = validate_str(case);
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].message(), "missing field `ghostList`");
+        }
+
+        #[test]
+        fn problems_with_path_and_position() {
+            let case = r#"{
+  "update": "2023-12-18T07:18:31.808Z",
+  "ghostList": [
+    {"directory": "a", "sakuraName": "", "keroName": ""},
+    {"directory": "b", "sakuraName": 1, "keroName": ""},
+    {"directory": "c", "sakuraName": "c"},
+    {"directory": "d", "sakuraName": "d", "keroName": "d"},
+    {"directory": "d", "sakuraName": "d", "keroName": "d"}
+  ]
+}"#;
+            let result = validate_str(case);
+            assert_eq!(
+                result.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+                vec![
+                    "4:38: $.ghostList[0].sakuraName: `sakuraName` is empty",
+                    "5:38: $.ghostList[1].sakuraName: expected a string, found a number",
+                    "6:5: $.ghostList[2]: missing field `keroName`",
+                    "8:5: $.ghostList[4]: duplicate of $.ghostList[3]",
+                ]
+            );
+            assert_eq!(result[0].path(), "$.ghostList[0].sakuraName");
+        }
+    }
+
+    mod no_kero {
+        use super::*;
+
+        #[test]
+        fn not_duplicate_when_no_kero_and_blank_kero() {
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"d","sakuraName":"s","keroName":"","noKero":true},{"directory":"d","sakuraName":"s","keroName":"","blankKero":true}]}"#;
+            assert!(validate_str(case).is_empty());
+        }
+
+        #[test]
+        fn duplicate_when_no_kero_and_blank_kero_of_older_list() {
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"d","sakuraName":"s","keroName":"","noKero":true},{"directory":"d","sakuraName":"s","keroName":""}]}"#;
+            let result = validate_str(case);
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].path(), "$.ghostList[1]");
+        }
+
+        #[test]
+        fn problem_when_no_kero_is_not_boolean() {
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"d","sakuraName":"s","keroName":"","noKero":"yes"}]}"#;
+            let result = validate_str(case);
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].path(), "$.ghostList[0].noKero");
+        }
+    }
+
+    mod duplicate {
+        use super::*;
+
+        #[test]
+        fn problem_when_directories_differ_only_in_case() {
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"Emily","sakuraName":"s","keroName":""},{"directory":"emily ","sakuraName":"s","keroName":""}]}"#;
+            let result = validate_str(case);
+            assert_eq!(result.len(), 1);
+            assert_eq!(
+                result[0].to_string(),
+                "1:104: $.ghostList[1]: duplicate of $.ghostList[0]"
+            );
+        }
+    }
+
+    mod annotations {
+        use super::*;
+
+        #[test]
+        fn problem_when_wrong_types() {
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"d","sakuraName":"s","keroName":"","tags":["ok",1],"note":false}]}"#;
+            let result = validate_str(case);
+            assert_eq!(
+                result.iter().map(|v| v.path().as_str()).collect::<Vec<_>>(),
+                vec!["$.ghostList[0].tags[1]", "$.ghostList[0].note"]
+            );
+        }
+    }
+
+    mod tombstones {
+        use super::*;
+
+        #[test]
+        fn problem_when_missing_fields() {
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[],"tombstones":[{"ghost":{"directory":"d","sakuraName":"s","keroName":""},"erasedAt":"2023-12-18T07:18:31.808Z"},{"erasedAt":1}]}"#;
+            let result = validate_str(case);
+            assert_eq!(
+                result.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+                vec![
+                    "1:164: $.tombstones[1]: missing field `ghost`",
+                    "1:176: $.tombstones[1].erasedAt: expected a string, found a number",
+                ]
+            );
+        }
+    }
+
+    mod validate_ndjson_str {
+        use super::*;
+
+        #[test]
+        fn nothing_when_valid_str() {
+            let case = "{\"update\":\"2023-12-18T07:18:31.808Z\"}\n\
+                        {\"directory\":\"a\",\"sakuraName\":\"s\",\"keroName\":\"k\"}\n";
+            assert!(validate_ndjson_str(case).is_empty());
+
+            let case = "{\"directory\":\"a\",\"sakuraName\":\"s\",\"keroName\":\"k\"}\n";
+            assert!(validate_ndjson_str(case).is_empty());
+        }
+
+        #[test]
+        fn problems_with_line_of_each_entry() {
+            let case = "{\"update\":\"x\"}\n\
+                        {\"directory\":\"a\",\"sakuraName\":\"\",\"keroName\":\"k\"}\n\
+                        \n\
+                        {\"directory\":\"a\",\"sakuraName\":\"\",\"keroName\":\"k\"}\n\
+                        {\"directory\":\n";
+            let result = validate_ndjson_str(case);
+            let result = result.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            assert_eq!(
+                result[..4],
+                vec![
+                    "1:11: $.update: `x` is not a RFC 3339 date-time",
+                    "2:31: $.ghostList[0].sakuraName: `sakuraName` is empty",
+                    "4:1: $.ghostList[1]: duplicate of $.ghostList[0]",
+                    "4:31: $.ghostList[1].sakuraName: `sakuraName` is empty",
+                ]
+            );
+            assert!(result[4].starts_with("5:"));
+            assert_eq!(result.len(), 5);
+        }
+    }
+
+    mod salvage_str {
+        use super::*;
+
+        #[test]
+        fn same_as_strict_when_valid_str() {
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"d","sakuraName":"s","keroName":"k"}],"source":"a"}"#;
+            let (json, problems) = salvage_str(case);
+            assert!(problems.is_empty());
+            assert_eq!(json, serde_json::from_str::<GhostJson>(case).unwrap());
+        }
+
+        #[test]
+        fn skipping_entries_of_wrong_types() {
+            let case = r#"{
+  "ghostList": [
+    {"directory": "a", "sakuraName": "a", "keroName": ""},
+    {"directory": "b", "sakuraName": 1, "keroName": ""},
+    {"directory": "c", "sakuraName": "c", "keroName": tru},
+    {"directory": "d", "sakuraName": "d", "keroName": "d"}
+  ]
+}"#;
+            let (json, problems) = salvage_str(case);
+            let directories: Vec<&String> =
+                json.ghost_list().iter().map(|v| v.directory()).collect();
+            assert_eq!(directories, vec!["a", "d"]);
+            assert!(json.update_time().is_none());
+
+            let problems: Vec<String> = problems.iter().map(|v| v.to_string()).collect();
+            assert_eq!(problems.len(), 4);
+            assert!(problems[0].starts_with("1:1: $: missing field `update`"));
+            assert!(problems[1].starts_with("4:5: $.ghostList[1]: skipped: "));
+            assert!(problems[2].starts_with("5:5: $.ghostList[2]: skipped: "));
+            assert!(problems[3].starts_with("5:58: $: expected ident"));
+        }
+
+        #[test]
+        fn recovering_entries_before_truncation() {
+            let case = r#"{"update":"2023-12-18T07:18:31.808Z","ghostList":[{"directory":"a","sakuraName":"a","keroName":""},{"directory":"b","sakuraN"#;
+            let (json, problems) = salvage_str(case);
+            assert_eq!(json.update(), "2023-12-18T07:18:31.808Z");
+            assert_eq!(json.ghost_list().len(), 1);
+            assert_eq!(problems.len(), 1);
+            assert_eq!(problems[0].path(), "$");
+        }
+
+        #[test]
+        fn skipping_deeply_nested_values() {
+            let case = format!(
+                r#"{{"update":"2023-12-18T07:18:31.808Z","ghostList":[{{"directory":"a","sakuraName":"a","keroName":""}},{}{},{{"directory":"b","sakuraName":"b","keroName":""}}]}}"#,
+                "[".repeat(200_000),
+                "]".repeat(200_000)
+            );
+            let (json, problems) = salvage_str(&case);
+            let directories: Vec<&String> =
+                json.ghost_list().iter().map(|v| v.directory()).collect();
+            assert_eq!(directories, vec!["a", "b"]);
+            assert!(problems
+                .iter()
+                .any(|v| v.message().contains("nested deeper than 128 levels")));
+        }
+
+        #[test]
+        fn not_crashing_when_deeply_nested_and_truncated() {
+            let case = "[".repeat(200_000);
+            let (json, problems) = salvage_str(&case);
+            assert!(json.ghost_list().is_empty());
+            assert!(!problems.is_empty());
+        }
+    }
+
+    mod salvage_ndjson_str {
+        use super::*;
+
+        #[test]
+        fn skipping_broken_lines() {
+            let case = "{\"update\":\"2023-12-18T07:18:31.808Z\"}\n{\"directory\":\"a\",\"sakuraName\":\"a\",\"keroName\":\"\"}\n{\"directory\":\"b\",\n{\"directory\":\"c\",\"sakuraName\":false}\n{\"directory\":\"d\",\"sakuraName\":\"d\",\"keroName\":\"\"}\n";
+            let (json, problems) = salvage_ndjson_str(case);
+            assert_eq!(json.update(), "2023-12-18T07:18:31.808Z");
+            let directories: Vec<&String> =
+                json.ghost_list().iter().map(|v| v.directory()).collect();
+            assert_eq!(directories, vec!["a", "d"]);
+            let lines: Vec<usize> = problems.iter().map(|v| v.line).collect();
+            assert_eq!(lines, vec![3, 4]);
+        }
+    }
+
+    mod locations {
+        use super::*;
+
+        #[test]
+        fn position_of_nested_value() {
+            let case = "{\"a\": [1, {\"b\": \"テスト\", \"c\": true}]}";
+            let locations = Locations::new(case);
+            assert_eq!(locations.position("$"), (1, 1));
+            assert_eq!(locations.position("$.a"), (1, 7));
+            assert_eq!(locations.position("$.a[1].b"), (1, 17));
+            assert_eq!(locations.position("$.a[1].c"), (1, 29));
+        }
+    }
+}